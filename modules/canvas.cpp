@@ -3,20 +3,29 @@
 /**IT_START**/
 
 import "render" {
-    func allocImage() -> s32;
-    func updateImage(s32, s32, s32);
+    func setCheckerboard(u1);
+    func loadImage(string, s32) -> s32;
+    func freeImage(s32);
+    func strokeRect(f32, f32, f32, f32, f32, s32);
+    func screenWidth() -> s32;
+    func screenHeight() -> s32;
 }
 import "input" {
     func mouseIsDown() -> u1;
     func mouseWentDown() -> u1;
+    func buttonIsDown(s32) -> u1;
+    func buttonWentDown(s32) -> u1;
     func mouseX() -> s32;
     func mouseY() -> s32;
 }
-// TO DO: rewrite canvas.cpp to USE Texture modules
-// TO DO: fix C++ parser/generator as well
+import "commands" {
+    func registerCommand(string) -> s32;
+    func commandTriggered(s32) -> s32;
+}
 type Texture = import "texture" {
 // import "texture" {
     func init(s32, s32);
+    func loadFrom(s32, s32, s32);
 
     // type Color = struct { r: u8, g: u8, b: u8, a: u8 };
     // type Color = s32;
@@ -29,49 +38,56 @@ type Texture = import "texture" {
 export {
     func init();
     func update();
+    func onResize(s32, s32);
 }
 
 /**IT_END**/
 
-// TODO: autogenerate this
-#define IMPORT(ns, n) __attribute__((import_module(ns), import_name(n)))
-using _Texture = void*;
-IMPORT("texture", "_construct") _Texture Texture_construct();
-IMPORT("texture", "init") void init(_Texture, int, int);
-IMPORT("texture", "getPixel") int getPixel(_Texture, int, int);
-IMPORT("texture", "setPixel") void setPixel(_Texture, int, int, int);
-IMPORT("texture", "draw") void draw(_Texture);
-class Texture {
-    _Texture data;
-public:
-    // Texture() : data(nullptr) {}
-    Texture() : data(Texture_construct()) {}
-    void init(int _1, int _2) {
-        return ::init(data, _1, _2);
-    }
-    int getPixel(int _1, int _2) {
-        return ::getPixel(data, _1, _2);
-    }
-    void setPixel(int _1, int _2, int _3) {
-        return ::setPixel(data, _1, _2, _3);
-    }
-    void draw() {
-        return ::draw(data);
-    }
-};
-
 typedef unsigned char u8;
 
-// TODO: programmatically
-int screenWidth = 400;
-int screenHeight = 300;
+// Where texture's draw() puts it: the middle quarter of the screen (our viewport).
+// Mouse positions arrive relative to the viewport.
+int canvasX, canvasY, canvasWidth, canvasHeight;
 
 Texture tex;
-const int width = 16;
-const int height = 16;
+// Size of the texture being edited; a blank one unless there's an image to start from
+int width = 16;
+int height = 16;
+const char* startImagePath = "resources/images/canvas.png";
+
+void onResize(int w, int h) {
+    canvasX = w / 4;
+    canvasY = h / 4;
+    canvasWidth = w / 2;
+    canvasHeight = h / 2;
+}
+
+// What the left button does
+enum Tool { Brush, Eraser };
+Tool tool = Brush;
+
+// The texture as it was before each stroke, oldest first
+const int maxUndo = 32;
+int* undoStack[maxUndo];
+int undoCount = 0;
+
+int undoCommand, brushCommand, eraserCommand;
 
 void init() {
+    onResize(screenWidth(), screenHeight());
+    undoCommand = registerCommand("canvas.undo");
+    brushCommand = registerCommand("canvas.brush");
+    eraserCommand = registerCommand("canvas.eraser");
     tex = Texture();
+    int size[2];
+    int image = loadImage(startImagePath, (int)size);
+    if (image > 0) {
+        width = size[0];
+        height = size[1];
+        tex.loadFrom(image, width, height);
+        freeImage(image);
+        return;
+    }
     tex.init(width, height);
     for (int x = 0; x < width; ++x) {
         for (int y = 0; y < height; ++y) {
@@ -80,16 +96,75 @@ void init() {
     }
 }
 
-void paint(int x, int y) {
-    int i = x * width / screenWidth;
-    int j = y * height / screenHeight;
-    int color = 0xfff00fff; // TODO: see why this shows as white
+// SDL's number for the right mouse button
+const int rightButton = 3;
+
+bool inCanvas(int x, int y) {
+    return x >= 0 && y >= 0 && x < canvasWidth && y < canvasHeight;
+}
+
+void paint(int x, int y, int color) {
+    if (!inCanvas(x, y)) {
+        return;
+    }
+    int i = x * width / canvasWidth;
+    int j = y * height / canvasHeight;
     tex.setPixel(i, j, color);
 }
 
+void pushUndo() {
+    if (undoCount == maxUndo) {
+        delete[] undoStack[0];
+        for (int i = 1; i < maxUndo; ++i) {
+            undoStack[i - 1] = undoStack[i];
+        }
+        undoCount--;
+    }
+    int* pixels = new int[width * height];
+    for (int x = 0; x < width; ++x) {
+        for (int y = 0; y < height; ++y) {
+            pixels[y * width + x] = tex.getPixel(x, y);
+        }
+    }
+    undoStack[undoCount++] = pixels;
+}
+
+void undo() {
+    if (undoCount == 0) {
+        return;
+    }
+    int* pixels = undoStack[--undoCount];
+    for (int x = 0; x < width; ++x) {
+        for (int y = 0; y < height; ++y) {
+            tex.setPixel(x, y, pixels[y * width + x]);
+        }
+    }
+    delete[] pixels;
+}
+
 void update() {
+    if (commandTriggered(brushCommand)) {
+        tool = Brush;
+    }
+    if (commandTriggered(eraserCommand)) {
+        tool = Eraser;
+    }
+    for (int i = commandTriggered(undoCommand); i > 0; --i) {
+        undo();
+    }
+
+    int x = mouseX() - canvasX;
+    int y = mouseY() - canvasY;
+    if ((mouseWentDown() || buttonWentDown(rightButton)) && inCanvas(x, y)) {
+        pushUndo();
+    }
+    // Left uses the tool, right always erases
     if (mouseIsDown()) {
-        paint(mouseX(), mouseY());
+        paint(x, y, tool == Brush ? 0xfff00fff : 0); // 0xAABBGGRR
+    } else if (buttonIsDown(rightButton)) {
+        paint(x, y, 0);
     }
+    setCheckerboard(true);
     tex.draw();
+    strokeRect(canvasX - 2, canvasY - 2, canvasWidth + 4, canvasHeight + 4, 2, 0xff808080);
 }
@@ -1,83 +1,245 @@
-// Reads event-based mouse input and provides a polling-based API
+// Reads event-based mouse, keyboard and text input and provides a polling-based API
 
 /**IT_START**/
 
 export {
     func update();
-    func onMouseEvent(s32, s32, s32);
-    func onKeyEvent(s32, s32);
+    func onMouseEvent(s32, s32, s32, s32, s32);
+    func onMouseMotion(s32, s32);
+    func onMouseWheel(f32, f32);
+    func onKeyEvent(s32, s32, s32, s32);
+    func onTextInput(s32);
+    func onTextEditing(s32, s32);
+    func onTextEditingByte(s32);
+    func onFocus(s32);
 
     func mouseIsDown() -> u1;
     func mouseWentDown() -> u1;
     func mouseWentUp() -> u1;
     func mouseX() -> s32;
     func mouseY() -> s32;
+    func mouseIsOver() -> u1;
+    func mouseDeltaX() -> s32;
+    func mouseDeltaY() -> s32;
+    func buttonIsDown(s32) -> u1;
+    func buttonWentDown(s32) -> u1;
+    func buttonWentUp(s32) -> u1;
+    func buttonClicks(s32) -> s32;
+    func wheelX() -> f32;
+    func wheelY() -> f32;
 
     func keyWentDown(s8) -> u1;
+    func scancodeIsDown(s32) -> u1;
+    func scancodeWentDown(s32) -> u1;
+    func scancodeWentUp(s32) -> u1;
+    func scancodeRepeats(s32) -> s32;
+    func keycodeIsDown(s32) -> u1;
+    func keycodeWentDown(s32) -> u1;
+    func keycodeWentUp(s32) -> u1;
+    func modifiers() -> s32;
+    func hasFocus() -> u1;
+
+    func textLength() -> s32;
+    func textByte(s32) -> s32;
+    func compositionLength() -> s32;
+    func compositionByte(s32) -> s32;
+    func compositionCursor() -> s32;
+    func compositionSelection() -> s32;
 }
 
 /**IT_END**/
 
-// Mouse data
-bool isMouseDown = false;
-bool wasMouseDown = false;
+// Mouse data, buttons by SDL number: 1 left, 2 middle, 3 right, 4 and 5 the extra ones
+const int numButtons = 6;
+const int leftButton = 1;
+bool isButtonDown[numButtons];
+// Since the last update, so clicks shorter than a frame still register
+bool buttonWentDownSinceUpdate[numButtons];
+bool buttonWentUpSinceUpdate[numButtons];
+// 1 for a single click, 2 for a double click, and so on, as of the last press
+int clicks[numButtons];
 int xPos = 0;
 int yPos = 0;
+// Whether the pointer's over this component, as of the last event
+bool mouseOver = false;
+// Since the last update. It's the pointer's motion, so it stops where the pointer
+// does: at the edge of the screen while a button's held, else of the window.
+int xMotion = 0;
+int yMotion = 0;
+float xWheel = 0;
+float yWheel = 0;
+
+// Keyboard data, by SDL scancode (physical key)
+const int numScancodes = 512;
+bool isKeyDown[numScancodes];
+// Since the last update, so taps shorter than a frame still register
+bool keyWentDownSinceUpdate[numScancodes];
+bool keyWentUpSinceUpdate[numScancodes];
+int keyRepeats[numScancodes];
+// What each key last typed as, in the current layout
+int keycodes[numScancodes];
+int mods = 0;
+// Keys only come in while this component has the keyboard focus
+bool focused = false;
 
-// Keyboard data
-const int numKeys = 256;
-bool isKeyDown[numKeys];
-bool wasKeyDown[numKeys];
+// Text typed since the last update, UTF-8
+const int maxText = 256;
+char text[maxText];
+int textLen = 0;
+// Set once a character doesn't fit, dropping it and everything after
+bool textFull = false;
+// Text being composed with an IME, UTF-8. The cursor and selection are in bytes.
+char composition[maxText];
+int compositionLen = 0;
+bool compositionFull = false;
+int cursor = 0;
+int selection = 0;
 
 void update() {
-    wasMouseDown = isMouseDown;
+    for (int i = 0; i < numButtons; ++i) {
+        buttonWentDownSinceUpdate[i] = false;
+        buttonWentUpSinceUpdate[i] = false;
+    }
+    xMotion = 0;
+    yMotion = 0;
+    xWheel = 0;
+    yWheel = 0;
 
-    for (int i = 0; i < numKeys; ++i) {
-        wasKeyDown[i] = isKeyDown[i];
+    for (int i = 0; i < numScancodes; ++i) {
+        keyWentDownSinceUpdate[i] = false;
+        keyWentUpSinceUpdate[i] = false;
+        keyRepeats[i] = 0;
     }
+    textLen = 0;
+    textFull = false;
 }
 
 // ----------------
 // Mouse input
 // TODO: enums for events, maybe structure?
-void onMouseEvent(int eventId, int x, int y) {
+// Moves have no button, downs and ups have the button and its click count.
+// Leaves come when the pointer moves on to something else.
+void onMouseEvent(int eventId, int x, int y, int button, int clickCount) {
     xPos = x;
     yPos = y;
+    mouseOver = eventId != 3;
+    if (button < 0 || button >= numButtons) return;
     switch (eventId) {
         case 0: { // move event
             break;
         }
         case 1: { // down event
-            isMouseDown = true;
+            isButtonDown[button] = true;
+            buttonWentDownSinceUpdate[button] = true;
+            clicks[button] = clickCount;
             break;
         }
         case 2: { // up event
-            isMouseDown = false;
+            isButtonDown[button] = false;
+            buttonWentUpSinceUpdate[button] = true;
+            break;
+        }
+        case 3: { // leave event
             break;
         }
     }
 }
 
-void onKeyEvent(int eventId, int key) {
-    if (key >= numKeys) return;
+// In pixels, y up like pixel space
+void onMouseMotion(int dx, int dy) {
+    xMotion += dx;
+    yMotion += dy;
+}
+
+// In wheel ticks, fractional for trackpads. Positive y scrolls up, positive x right.
+void onMouseWheel(float x, float y) {
+    xWheel += x;
+    yWheel += y;
+}
+
+// Events are 0 for down, 1 for up, 2 for an auto-repeat. Modifiers are SDL's KMOD_ bits.
+void onKeyEvent(int eventId, int scancode, int keycode, int modifiers) {
+    mods = modifiers;
+    if (scancode < 0 || scancode >= numScancodes) return;
+    keycodes[scancode] = keycode;
     switch (eventId) {
-        case 0: // key down
-            isKeyDown[key] = true;
+        case 0:
+            isKeyDown[scancode] = true;
+            keyWentDownSinceUpdate[scancode] = true;
             break;
         case 1:
-            isKeyDown[key] = false;
+            isKeyDown[scancode] = false;
+            keyWentUpSinceUpdate[scancode] = true;
+            break;
+        case 2:
+            keyRepeats[scancode]++;
             break;
     }
 }
 
+// Losing the focus lets go of every key, since their ups will go elsewhere
+void onFocus(int hasFocus) {
+    focused = hasFocus;
+    if (focused) return;
+    for (int i = 0; i < numScancodes; ++i) {
+        if (isKeyDown[i]) {
+            isKeyDown[i] = false;
+            keyWentUpSinceUpdate[i] = true;
+        }
+    }
+    mods = 0;
+    compositionLen = 0;
+    compositionFull = false;
+}
+
+// ----------------
+// Text input, a byte at a time
+
+// Bytes in the UTF-8 sequence that starts with `lead`
+int utf8Length(int lead) {
+    if (lead < 0x80) return 1;
+    if ((lead & 0xe0) == 0xc0) return 2;
+    if ((lead & 0xf0) == 0xe0) return 3;
+    return 4;
+}
+
+// Adds a byte of UTF-8 to `buf`, stopping at the first character that doesn't
+// fit whole, so what's kept never ends partway through one
+void appendUtf8(char* buf, int* len, bool* full, int byte) {
+    bool continuation = (byte & 0xc0) == 0x80;
+    if (!continuation && !*full) {
+        *full = *len + utf8Length(byte) > maxText;
+    }
+    if (!*full && *len < maxText) {
+        buf[(*len)++] = byte;
+    }
+}
+
+void onTextInput(int byte) {
+    appendUtf8(text, &textLen, &textFull, byte);
+}
+
+// Starts over on the composition, whose bytes follow
+void onTextEditing(int cursorByte, int selectionBytes) {
+    compositionLen = 0;
+    compositionFull = false;
+    cursor = cursorByte;
+    selection = selectionBytes;
+}
+
+void onTextEditingByte(int byte) {
+    appendUtf8(composition, &compositionLen, &compositionFull, byte);
+}
+
+// The left button
 bool mouseIsDown() {
-    return isMouseDown;
+    return isButtonDown[leftButton];
 }
 bool mouseWentDown() {
-    return isMouseDown && !wasMouseDown;
+    return buttonWentDownSinceUpdate[leftButton];
 }
 bool mouseWentUp() {
-    return !isMouseDown && wasMouseDown;
+    return buttonWentUpSinceUpdate[leftButton];
 }
 int mouseX() {
     return xPos;
@@ -85,9 +247,97 @@ int mouseX() {
 int mouseY() {
     return yPos;
 }
+bool mouseIsOver() {
+    return mouseOver;
+}
+int mouseDeltaX() {
+    return xMotion;
+}
+int mouseDeltaY() {
+    return yMotion;
+}
+
+bool buttonIsDown(int button) {
+    return button >= 0 && button < numButtons && isButtonDown[button];
+}
+bool buttonWentDown(int button) {
+    return button >= 0 && button < numButtons && buttonWentDownSinceUpdate[button];
+}
+bool buttonWentUp(int button) {
+    return button >= 0 && button < numButtons && buttonWentUpSinceUpdate[button];
+}
+int buttonClicks(int button) {
+    return button >= 0 && button < numButtons ? clicks[button] : 0;
+}
+
+float wheelX() {
+    return xWheel;
+}
+float wheelY() {
+    return yWheel;
+}
 
 // -------------
 // Keyboard input
+bool scancodeIsDown(int scancode) {
+    return scancode >= 0 && scancode < numScancodes && isKeyDown[scancode];
+}
+bool scancodeWentDown(int scancode) {
+    return scancode >= 0 && scancode < numScancodes && keyWentDownSinceUpdate[scancode];
+}
+bool scancodeWentUp(int scancode) {
+    return scancode >= 0 && scancode < numScancodes && keyWentUpSinceUpdate[scancode];
+}
+int scancodeRepeats(int scancode) {
+    return scancode >= 0 && scancode < numScancodes ? keyRepeats[scancode] : 0;
+}
+
+// By keycode, i.e. what the key types in the current layout. Letters and
+// digits are their lowercase ASCII.
+bool keycodeIsDown(int keycode) {
+    for (int i = 0; i < numScancodes; ++i) {
+        if (keycodes[i] == keycode && isKeyDown[i]) return true;
+    }
+    return false;
+}
+bool keycodeWentDown(int keycode) {
+    for (int i = 0; i < numScancodes; ++i) {
+        if (keycodes[i] == keycode && keyWentDownSinceUpdate[i]) return true;
+    }
+    return false;
+}
+bool keycodeWentUp(int keycode) {
+    for (int i = 0; i < numScancodes; ++i) {
+        if (keycodes[i] == keycode && keyWentUpSinceUpdate[i]) return true;
+    }
+    return false;
+}
 bool keyWentDown(char key) {
-    return isKeyDown[key] && !wasKeyDown[key];
+    return keycodeWentDown(key);
+}
+
+int modifiers() {
+    return mods;
+}
+bool hasFocus() {
+    return focused;
+}
+
+int textLength() {
+    return textLen;
+}
+int textByte(int i) {
+    return i >= 0 && i < textLen ? (unsigned char)text[i] : 0;
+}
+int compositionLength() {
+    return compositionLen;
+}
+int compositionByte(int i) {
+    return i >= 0 && i < compositionLen ? (unsigned char)composition[i] : 0;
+}
+int compositionCursor() {
+    return cursor;
+}
+int compositionSelection() {
+    return selection;
 }
@@ -1,10 +1,14 @@
 /**IT_START**/
 
 import "render" {
-    func drawText(string);
+    func drawText(string, f32, f32, f32, s32);
+    func screenHeight() -> s32;
 }
 import "input" {
-    func keyWentDown(s8) -> u1;
+    func scancodeWentDown(s32) -> u1;
+    func scancodeRepeats(s32) -> s32;
+    func textLength() -> s32;
+    func textByte(s32) -> s32;
 }
 export {
     func update();
@@ -14,14 +18,29 @@ export {
 
 #include <string>
 
+// SDL_SCANCODE_BACKSPACE
+const int backspaceKey = 42;
+
 std::string text = "";
 
+// Drops the last character, which may be several UTF-8 bytes
+void erase() {
+    while (!text.empty()) {
+        char c = text.back();
+        text.pop_back();
+        if ((c & 0xc0) != 0x80) break;
+    }
+}
+
 void update() {
-    for (char c = 'a'; c < 'z'; ++c) {
-        if (keyWentDown(c)) {
-            text += c;
-        }
+    for (int i = 0; i < textLength(); ++i) {
+        text += (char)textByte(i);
+    }
+    int erases = scancodeRepeats(backspaceKey) + (scancodeWentDown(backspaceKey) ? 1 : 0);
+    for (int i = 0; i < erases; ++i) {
+        erase();
     }
 
-    drawText(text.c_str());
+    // Top-left of the window, in black
+    drawText(text.c_str(), 8, screenHeight() - 8, 16, 0xff000000);
 }
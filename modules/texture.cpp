@@ -4,11 +4,16 @@
 
 import "render" {
     func allocImage() -> s32;
+    func freeImage(s32);
     func updateImage(s32, s32, s32, s32);
+    func readImage(s32, s32, s32, s32);
+    func markImageDirty(s32, s32, s32, s32, s32, s32, s32, s32);
     func drawImage(s32);
 }
 export {
     func init(s32, s32);
+    func loadFrom(s32, s32, s32);
+    func deinit();
 
     // type Color = struct { r: u8, g: u8, b: u8, a: u8 };
     // type Color = s32;
@@ -23,6 +28,8 @@ export {
 
 typedef unsigned char u8;
 
+// Matches the host's pixel format: r, g, b, a bytes with straight alpha,
+// packed into an int as 0xAABBGGRR. Rows go bottom to top.
 struct Color {
     u8 r, g, b, a;
 
@@ -52,11 +59,30 @@ int w = 0;
 int h = 0;
 Color* texture = nullptr;
 
+void deinit() {
+    delete[] texture;
+    texture = nullptr;
+    if (imageId) {
+        freeImage(imageId);
+        imageId = 0;
+    }
+}
+
 void init(int _w, int _h) {
+    deinit();
     w = _w; h = _h;
-    auto old = texture;
     texture = new Color[w * h];
     imageId = allocImage();
+    // Full upload once to size the image, setPixel only uploads what changed
+    updateImage(imageId, (int)texture, w, h);
+}
+
+// Replaces the texture with a copy of a w*h image, e.g. one from loadImage.
+// The image stays with whoever owns it.
+void loadFrom(int image, int _w, int _h) {
+    init(_w, _h);
+    readImage(image, (int)texture, w, h);
+    updateImage(imageId, (int)texture, w, h);
 }
 
 int getPixel(int x, int y) {
@@ -65,7 +91,7 @@ int getPixel(int x, int y) {
 void setPixel(int x, int y, int color_) {
     Color color = Color::fromInt(color_);
     texture[x + w * y] = color;
-    updateImage(imageId, (int)texture, w, h);
+    markImageDirty(imageId, (int)texture, w, h, x, y, 1, 1);
 }
 
 void draw() {
@@ -0,0 +1,29 @@
+;; Adapts the WASI calls emscripten emits onto EdEd's host "env" module.
+;; Composed alongside the module it serves, see notes.compose
+
+(module
+  ;; Shares the served module's memory, so it's listed after that module
+  (import "main" "memory" (memory 0))
+  (import "env" "print" (func $print (param i32 i32)))
+  (import "env" "exit" (func $exit (param i32)))
+
+  ;; fd_write(fd, iovs, iovs_len, nwritten) -> errno
+  ;; Every fd goes to the host console
+  (func (export "fd_write") (param $fd i32) (param $iovs i32) (param $len i32) (param $nwritten i32) (result i32)
+    (local $total i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $len)))
+        (call $print
+          (i32.load (local.get $iovs))
+          (i32.load offset=4 (local.get $iovs)))
+        (local.set $total (i32.add (local.get $total) (i32.load offset=4 (local.get $iovs))))
+        (local.set $iovs (i32.add (local.get $iovs) (i32.const 8)))
+        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+        (br $next)))
+    (i32.store (local.get $nwritten) (local.get $total))
+    (i32.const 0))
+
+  (func (export "proc_exit") (param $code i32)
+    (call $exit (local.get $code)))
+)
@@ -0,0 +1,41 @@
+#version 330 core
+
+// Goes ahead of every component's custom shader, which defines
+//     vec4 effect(vec2 uv)
+// returning the straight-alpha color at uv. texel() samples the image being
+// drawn (or the screen, in a post pass); textureSize(Texture, 0) is its size.
+
+in vec2 uvPos;
+uniform sampler2D Texture;
+// Render targets hold premultiplied colors, uploaded images straight alpha
+uniform bool Premultiplied;
+// Straight alpha, like image data
+uniform vec4 Tint;
+// Composite over a transparency checkerboard instead of blending
+uniform bool Checkerboard;
+out vec4 Color;
+
+const float CHECK_SIZE = 8.0;
+
+// Straight alpha, whatever the image is stored as
+vec4 texel(vec2 uv) {
+    vec4 texel = texture(Texture, uv);
+    if (Premultiplied && texel.a > 0.0) {
+        texel.rgb /= texel.a;
+    }
+    return texel;
+}
+
+vec4 effect(vec2 uv);
+
+void main() {
+    vec4 color = clamp(effect(uvPos), 0.0, 1.0);
+    // Blending is premultiplied
+    color = vec4(color.rgb * color.a, color.a) * vec4(Tint.rgb * Tint.a, Tint.a);
+    if (Checkerboard) {
+        vec2 cell = floor(gl_FragCoord.xy / CHECK_SIZE);
+        float check = mod(cell.x + cell.y, 2.0) == 0.0 ? 0.8 : 0.6;
+        color = vec4(color.rgb + check * (1.0 - color.a), 1.0);
+    }
+    Color = color;
+}
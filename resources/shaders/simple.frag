@@ -1,7 +1,9 @@
 #version 330 core
 
+// Premultiplied
+in vec4 color;
 out vec4 Color;
 
 void main() {
-    Color = vec4(0.95f, 0.95f, 0.95f, 1.0f);
+    Color = color;
 }
@@ -1,7 +1,14 @@
 #version 330 core
 
-layout (location = 0) in vec3 Position;
+layout (location = 0) in vec2 Position;
+layout (location = 1) in vec4 VertexColor;
+
+// Pixels to clip space
+uniform mat4 Projection;
+
+out vec4 color;
 
 void main() {
-    gl_Position = vec4(Position, 1.0);
+    gl_Position = Projection * vec4(Position, 0.0, 1.0);
+    color = VertexColor;
 }
@@ -2,9 +2,27 @@
 
 in vec2 uvPos;
 uniform sampler2D Texture;
+// Render targets hold premultiplied colors, uploaded images straight alpha
+uniform bool Premultiplied;
+// Straight alpha, like image data
+uniform vec4 Tint;
+// Composite over a transparency checkerboard instead of blending
+uniform bool Checkerboard;
 out vec4 Color;
 
+const float CHECK_SIZE = 8.0;
+
 void main() {
-    float v = texture(Texture, uvPos).x;
-    Color = vec4(v, v, v, 1.0);
+    vec4 texel = texture(Texture, uvPos);
+    // Blending is premultiplied
+    if (!Premultiplied) {
+        texel = vec4(texel.rgb * texel.a, texel.a);
+    }
+    vec4 color = texel * vec4(Tint.rgb * Tint.a, Tint.a);
+    if (Checkerboard) {
+        vec2 cell = floor(gl_FragCoord.xy / CHECK_SIZE);
+        float check = mod(cell.x + cell.y, 2.0) == 0.0 ? 0.8 : 0.6;
+        color = vec4(color.rgb + check * (1.0 - color.a), 1.0);
+    }
+    Color = color;
 }
@@ -3,9 +3,16 @@
 layout (location = 0) in vec3 Position;
 layout (location = 1) in vec2 TexCoord;
 
+// Pixels to clip space
+uniform mat4 Projection;
+// Unit quad to pixels
+uniform mat4 Transform;
+// UV offset (xy) and size (zw) of the region to sample
+uniform vec4 SourceRect;
+
 out vec2 uvPos;
 
 void main() {
-    gl_Position = vec4(Position, 1.0);
-    uvPos = TexCoord;
+    gl_Position = Projection * Transform * vec4(Position, 1.0);
+    uvPos = SourceRect.xy + TexCoord * SourceRect.zw;
 }
@@ -0,0 +1,159 @@
+// Rust version of modules/canvas.cpp: a window in to a texture-editing context
+
+use eded::{commands::Command, input, render::{self, Image}, texture::Texture, Color};
+use std::cell::RefCell;
+
+// Size of the blank texture, if there's no image to start from
+const WIDTH: i32 = 16;
+const HEIGHT: i32 = 16;
+const START_IMAGE_PATH: &str = "resources/images/canvas.png";
+const MAX_UNDO: usize = 32;
+
+// What the left button does
+#[derive(Clone, Copy, PartialEq)]
+enum Tool {
+    Brush,
+    Eraser,
+}
+
+struct Commands {
+    undo: Command,
+    brush: Command,
+    eraser: Command,
+}
+
+struct Canvas {
+    tex: Texture,
+    w: i32,
+    h: i32,
+    tool: Tool,
+    // The texture as it was before each stroke, oldest first
+    undo_stack: Vec<Vec<Color>>,
+    commands: Commands,
+}
+
+// Where texture's draw() puts it: the middle quarter of the screen (our viewport).
+// Mouse positions arrive relative to the viewport.
+#[derive(Clone, Copy, Default)]
+struct Layout {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+thread_local! {
+    static CANVAS: RefCell<Option<Canvas>> = const { RefCell::new(None) };
+    static LAYOUT: RefCell<Layout> = const { RefCell::new(Layout { x: 0, y: 0, w: 0, h: 0 }) };
+}
+
+fn on_resize(w: i32, h: i32) {
+    LAYOUT.with(|l| *l.borrow_mut() = Layout { x: w / 4, y: h / 4, w: w / 2, h: h / 2 });
+}
+
+fn init() {
+    let (w, h) = render::screen_size();
+    on_resize(w, h);
+    let (tex, w, h) = match Image::load(START_IMAGE_PATH) {
+        Ok((image, w, h)) => {
+            let tex = Texture::new(w, h);
+            tex.load_from(&image, w, h);
+            (tex, w, h)
+        },
+        Err(_) => {
+            let tex = Texture::new(WIDTH, HEIGHT);
+            for x in 0..WIDTH {
+                for y in 0..HEIGHT {
+                    tex.set_pixel(x, y, Color::default());
+                }
+            }
+            (tex, WIDTH, HEIGHT)
+        },
+    };
+    let commands = Commands {
+        undo: Command::register("canvas.undo"),
+        brush: Command::register("canvas.brush"),
+        eraser: Command::register("canvas.eraser"),
+    };
+    let canvas = Canvas { tex, w, h, tool: Tool::Brush, undo_stack: Vec::new(), commands };
+    CANVAS.with(|c| *c.borrow_mut() = Some(canvas));
+}
+
+fn in_canvas(layout: Layout, x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && x < layout.w && y < layout.h
+}
+
+fn paint(canvas: &Canvas, layout: Layout, x: i32, y: i32, color: Color) {
+    if !in_canvas(layout, x, y) {
+        return;
+    }
+    let i = x * canvas.w / layout.w;
+    let j = y * canvas.h / layout.h;
+    canvas.tex.set_pixel(i, j, color);
+}
+
+impl Canvas {
+    fn push_undo(&mut self) {
+        if self.undo_stack.len() == MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+        let mut pixels = Vec::with_capacity((self.w * self.h) as usize);
+        for y in 0..self.h {
+            for x in 0..self.w {
+                pixels.push(self.tex.get_pixel(x, y));
+            }
+        }
+        self.undo_stack.push(pixels);
+    }
+
+    fn undo(&mut self) {
+        if let Some(pixels) = self.undo_stack.pop() {
+            for (i, &color) in pixels.iter().enumerate() {
+                let i = i as i32;
+                self.tex.set_pixel(i % self.w, i / self.w, color);
+            }
+        }
+    }
+}
+
+fn update() {
+    CANVAS.with(|c| {
+        if let Some(canvas) = c.borrow_mut().as_mut() {
+            let layout = LAYOUT.with(|l| *l.borrow());
+            if canvas.commands.brush.triggered() > 0 {
+                canvas.tool = Tool::Brush;
+            }
+            if canvas.commands.eraser.triggered() > 0 {
+                canvas.tool = Tool::Eraser;
+            }
+            for _ in 0..canvas.commands.undo.triggered() {
+                canvas.undo();
+            }
+
+            let (x, y) = (input::mouse_x() - layout.x, input::mouse_y() - layout.y);
+            if (input::mouse_went_down() || input::button_went_down(input::RIGHT_BUTTON)) && in_canvas(layout, x, y) {
+                canvas.push_undo();
+            }
+            // Left uses the tool, right always erases
+            if input::mouse_is_down() {
+                let color = match canvas.tool {
+                    Tool::Brush => Color::rgb(0xff, 0x0f, 0xf0),
+                    Tool::Eraser => Color::default(),
+                };
+                paint(canvas, layout, x, y, color);
+            } else if input::button_is_down(input::RIGHT_BUTTON) {
+                paint(canvas, layout, x, y, Color::default());
+            }
+            render::set_checkerboard(true);
+            canvas.tex.draw();
+            render::stroke_rect((layout.x - 2) as f32, (layout.y - 2) as f32,
+                (layout.w + 4) as f32, (layout.h + 4) as f32, 2.0, Color::rgb(0x80, 0x80, 0x80));
+        }
+    })
+}
+
+eded::exports! {
+    func init() => init;
+    func update() => update;
+    func onResize(w: s32, h: s32) => on_resize;
+}
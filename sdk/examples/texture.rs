@@ -0,0 +1,70 @@
+// Rust version of modules/texture.cpp: a 2D array of pixel data
+
+use eded::{render::{self, Image}, Color};
+use std::cell::RefCell;
+
+struct Texture {
+    w: i32,
+    h: i32,
+    pixels: Vec<Color>,
+    image: Image,
+}
+
+thread_local! {
+    static TEXTURE: RefCell<Option<Texture>> = const { RefCell::new(None) };
+}
+
+fn init(w: i32, h: i32) {
+    let pixels = vec![Color::default(); (w * h) as usize];
+    let image = Image::alloc();
+    image.update(&pixels, w, h);
+    // Replacing the old texture drops its Image, which frees it on the host
+    TEXTURE.with(|t| *t.borrow_mut() = Some(Texture { w, h, pixels, image }));
+}
+
+fn load_from(image: i32, w: i32, h: i32) {
+    let mut pixels = vec![Color::default(); (w * h) as usize];
+    render::read_image(image, &mut pixels, w, h);
+    let image = Image::alloc();
+    image.update(&pixels, w, h);
+    TEXTURE.with(|t| *t.borrow_mut() = Some(Texture { w, h, pixels, image }));
+}
+
+fn deinit() {
+    TEXTURE.with(|t| *t.borrow_mut() = None);
+}
+
+fn get_pixel(x: i32, y: i32) -> i32 {
+    TEXTURE.with(|t| {
+        let t = t.borrow();
+        let t = t.as_ref().expect("getPixel before init");
+        t.pixels[(x + t.w * y) as usize].to_i32()
+    })
+}
+
+fn set_pixel(x: i32, y: i32, color: i32) {
+    TEXTURE.with(|t| {
+        let mut t = t.borrow_mut();
+        let t = t.as_mut().expect("setPixel before init");
+        let idx = (x + t.w * y) as usize;
+        t.pixels[idx] = Color::from_i32(color);
+        t.image.mark_dirty(&t.pixels, t.w, t.h, [x, y, 1, 1]);
+    })
+}
+
+fn draw() {
+    TEXTURE.with(|t| {
+        if let Some(t) = t.borrow().as_ref() {
+            t.image.draw();
+        }
+    })
+}
+
+eded::exports! {
+    func init(w: s32, h: s32) => init;
+    func loadFrom(image: s32, w: s32, h: s32) => load_from;
+    func deinit() => deinit;
+    func getPixel(x: s32, y: s32) -> s32 => get_pixel;
+    func setPixel(x: s32, y: s32, color: s32) => set_pixel;
+    func draw() => draw;
+}
@@ -0,0 +1,31 @@
+// Bindings for the host "commands" module
+
+use crate::marshal::CString;
+
+mod raw {
+    #[link(wasm_import_module = "commands")]
+    extern "C" {
+        pub fn registerCommand(name: i32) -> i32;
+        pub fn commandTriggered(id: i32) -> i32;
+    }
+}
+
+// A named command, which goes off when the keys bound to it are pressed. The
+// bindings are the user's, in the host's keybindings file.
+#[derive(Clone, Copy)]
+pub struct Command {
+    id: i32,
+}
+impl Command {
+    // Names are namespaced by convention, as in "canvas.undo". Registering a name
+    // again gets the same command.
+    pub fn register(name: &str) -> Command {
+        let name = CString::new(name);
+        Command { id: unsafe { raw::registerCommand(name.as_ptr()) } }
+    }
+
+    // Times it went off since last frame; held keys repeat
+    pub fn triggered(&self) -> i32 {
+        unsafe { raw::commandTriggered(self.id) }
+    }
+}
@@ -0,0 +1,166 @@
+// Bindings for the "input" component
+
+use crate::marshal::to_bool;
+
+mod raw {
+    #[link(wasm_import_module = "input")]
+    extern "C" {
+        pub fn mouseIsDown() -> i32;
+        pub fn mouseWentDown() -> i32;
+        pub fn mouseWentUp() -> i32;
+        pub fn mouseX() -> i32;
+        pub fn mouseY() -> i32;
+        pub fn mouseIsOver() -> i32;
+        pub fn mouseDeltaX() -> i32;
+        pub fn mouseDeltaY() -> i32;
+        pub fn buttonIsDown(button: i32) -> i32;
+        pub fn buttonWentDown(button: i32) -> i32;
+        pub fn buttonWentUp(button: i32) -> i32;
+        pub fn buttonClicks(button: i32) -> i32;
+        pub fn wheelX() -> f32;
+        pub fn wheelY() -> f32;
+        pub fn keyWentDown(key: i32) -> i32;
+        pub fn scancodeIsDown(scancode: i32) -> i32;
+        pub fn scancodeWentDown(scancode: i32) -> i32;
+        pub fn scancodeWentUp(scancode: i32) -> i32;
+        pub fn scancodeRepeats(scancode: i32) -> i32;
+        pub fn keycodeIsDown(keycode: i32) -> i32;
+        pub fn keycodeWentDown(keycode: i32) -> i32;
+        pub fn keycodeWentUp(keycode: i32) -> i32;
+        pub fn modifiers() -> i32;
+        pub fn hasFocus() -> i32;
+        pub fn textLength() -> i32;
+        pub fn textByte(i: i32) -> i32;
+        pub fn compositionLength() -> i32;
+        pub fn compositionByte(i: i32) -> i32;
+        pub fn compositionCursor() -> i32;
+        pub fn compositionSelection() -> i32;
+    }
+}
+
+// Mouse buttons, numbered as SDL does
+pub const LEFT_BUTTON: i32 = 1;
+pub const MIDDLE_BUTTON: i32 = 2;
+pub const RIGHT_BUTTON: i32 = 3;
+pub const X1_BUTTON: i32 = 4;
+pub const X2_BUTTON: i32 = 5;
+
+// Modifier masks for `modifiers()`, each covering the left and right keys
+pub const SHIFT: i32 = 0x0003;
+pub const CTRL: i32 = 0x00c0;
+pub const ALT: i32 = 0x0300;
+pub const GUI: i32 = 0x0c00;
+
+pub fn mouse_is_down() -> bool {
+    to_bool(unsafe { raw::mouseIsDown() })
+}
+pub fn mouse_went_down() -> bool {
+    to_bool(unsafe { raw::mouseWentDown() })
+}
+pub fn mouse_went_up() -> bool {
+    to_bool(unsafe { raw::mouseWentUp() })
+}
+pub fn mouse_x() -> i32 {
+    unsafe { raw::mouseX() }
+}
+pub fn mouse_y() -> i32 {
+    unsafe { raw::mouseY() }
+}
+// Whether the pointer's over this component, and not something on top of it.
+// During a drag, positions keep coming from outside.
+pub fn mouse_is_over() -> bool {
+    to_bool(unsafe { raw::mouseIsOver() })
+}
+// Relative motion since last frame, y up. Stops where the pointer does, at the
+// edge of the screen while a button's held, else of the window.
+pub fn mouse_delta() -> (i32, i32) {
+    unsafe { (raw::mouseDeltaX(), raw::mouseDeltaY()) }
+}
+
+pub fn button_is_down(button: i32) -> bool {
+    to_bool(unsafe { raw::buttonIsDown(button) })
+}
+pub fn button_went_down(button: i32) -> bool {
+    to_bool(unsafe { raw::buttonWentDown(button) })
+}
+pub fn button_went_up(button: i32) -> bool {
+    to_bool(unsafe { raw::buttonWentUp(button) })
+}
+// As of the button's last press: 1 for a single click, 2 for a double click, ...
+pub fn button_clicks(button: i32) -> i32 {
+    unsafe { raw::buttonClicks(button) }
+}
+
+// Wheel movement since last frame, in ticks. Positive x scrolls right, positive y up.
+pub fn wheel() -> (f32, f32) {
+    unsafe { (raw::wheelX(), raw::wheelY()) }
+}
+
+// By the character the key types, e.g. b'a'
+pub fn key_went_down(key: u8) -> bool {
+    to_bool(unsafe { raw::keyWentDown(key as i32) })
+}
+
+// Scancodes are SDL's, the physical key whatever the layout
+pub fn scancode_is_down(scancode: i32) -> bool {
+    to_bool(unsafe { raw::scancodeIsDown(scancode) })
+}
+pub fn scancode_went_down(scancode: i32) -> bool {
+    to_bool(unsafe { raw::scancodeWentDown(scancode) })
+}
+pub fn scancode_went_up(scancode: i32) -> bool {
+    to_bool(unsafe { raw::scancodeWentUp(scancode) })
+}
+// Auto-repeats since last frame, for keys held down
+pub fn scancode_repeats(scancode: i32) -> i32 {
+    unsafe { raw::scancodeRepeats(scancode) }
+}
+
+// Keycodes are SDL's, what the key types in the current layout
+pub fn keycode_is_down(keycode: i32) -> bool {
+    to_bool(unsafe { raw::keycodeIsDown(keycode) })
+}
+pub fn keycode_went_down(keycode: i32) -> bool {
+    to_bool(unsafe { raw::keycodeWentDown(keycode) })
+}
+pub fn keycode_went_up(keycode: i32) -> bool {
+    to_bool(unsafe { raw::keycodeWentUp(keycode) })
+}
+
+// SDL's KMOD_ bits, as of the last key event; see SHIFT, CTRL etc.
+pub fn modifiers() -> i32 {
+    unsafe { raw::modifiers() }
+}
+
+// Whether keys and text come to this component. Clicking a component gives it
+// the focus; export onFocusChanged(focused: s32) to hear when it moves.
+pub fn has_focus() -> bool {
+    to_bool(unsafe { raw::hasFocus() })
+}
+
+// Text typed since last frame
+pub fn text() -> String {
+    let bytes: Vec<u8> = (0..unsafe { raw::textLength() }).map(|i| unsafe { raw::textByte(i) } as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Text an IME is in the middle of composing, not yet typed
+pub struct Composition {
+    pub text: String,
+    // Byte offsets into `text`
+    pub cursor: usize,
+    pub selection: usize,
+}
+
+pub fn composition() -> Option<Composition> {
+    let len = unsafe { raw::compositionLength() };
+    if len == 0 {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..len).map(|i| unsafe { raw::compositionByte(i) } as u8).collect();
+    Some(Composition {
+        text: String::from_utf8_lossy(&bytes).into_owned(),
+        cursor: unsafe { raw::compositionCursor() } as usize,
+        selection: unsafe { raw::compositionSelection() } as usize,
+    })
+}
@@ -0,0 +1,22 @@
+// IT types, as seen from Rust. Names match the IT block spelling so that the
+// `exports!` macro can use them directly.
+#![allow(non_camel_case_types)]
+
+pub type u1 = bool;
+pub type s8 = i8;
+pub type u8 = core::primitive::u8;
+pub type s32 = i32;
+pub type u32 = core::primitive::u32;
+pub type f32 = core::primitive::f32;
+
+// Copies an IT block into a fixed-size array, for embedding as a custom section
+pub const fn to_bytes<const N: usize>(text: &str) -> [u8; N] {
+    let bytes = text.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < N {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    out
+}
@@ -0,0 +1,51 @@
+// Guest SDK for EdEd components written in Rust
+//
+// Wraps the host's import modules in typed, safe bindings, and provides the
+// `exports!` macro for declaring a component's exports and its IT block.
+
+pub mod commands;
+pub mod input;
+pub mod it;
+pub mod marshal;
+pub mod render;
+pub mod texture;
+pub mod time;
+
+pub use marshal::Color;
+
+// Declares a component's exports, using IT types for the signature:
+//
+//     eded::exports! {
+//         func init(w: s32, h: s32) => init;
+//         func getPixel(x: s32, y: s32) -> s32 => get_pixel;
+//     }
+//
+// Each entry becomes an unmangled wasm export forwarding to the named Rust
+// function in the same module. The matching IT block is embedded in the
+// module's "it" custom section.
+#[macro_export]
+macro_rules! exports {
+    ($(func $name:ident($($arg:ident: $ty:ident),*) $(-> $ret:ident)? => $func:ident;)*) => {
+        // Kept in their own module so export names can match the Rust functions'
+        mod __eded_exports {
+            $(
+                #[no_mangle]
+                #[allow(non_snake_case)]
+                pub extern "C" fn $name($($arg: $crate::it::$ty),*) $(-> $crate::it::$ret)? {
+                    super::$func($($arg),*)
+                }
+            )*
+        }
+
+        const _: () = {
+            const IT_TEXT: &str = concat!(
+                "export {\n",
+                $("    func ", stringify!($name), "(", stringify!($($ty),*), ")", $(" -> ", stringify!($ret),)? ";\n",)*
+                "}\n",
+            );
+            #[link_section = "it"]
+            #[used]
+            static IT_BLOCK: [u8; IT_TEXT.len()] = $crate::it::to_bytes(IT_TEXT);
+        };
+    };
+}
@@ -0,0 +1,55 @@
+// Conversions between Rust values and the host's wasm-level ABI
+
+// An RGBA pixel, laid out as 4 consecutive bytes in guest memory, with
+// straight (not premultiplied) alpha. Crosses the ABI as a single s32 with r
+// in the low byte (0xAABBGGRR), matching texture.cpp.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+impl Color {
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 0xff }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        i32::from_le_bytes([self.r, self.g, self.b, self.a])
+    }
+    pub fn from_i32(v: i32) -> Color {
+        let [r, g, b, a] = v.to_le_bytes();
+        Color { r, g, b, a }
+    }
+}
+
+// Host `string` parameters are pointers to NUL-terminated UTF-8.
+// Interior NULs would truncate the string, so they're dropped.
+pub struct CString {
+    bytes: Vec<u8>,
+}
+impl CString {
+    pub fn new(text: &str) -> CString {
+        let mut bytes: Vec<u8> = text.bytes().filter(|&b| b != 0).collect();
+        bytes.push(0);
+        CString { bytes }
+    }
+    pub fn as_ptr(&self) -> i32 {
+        self.bytes.as_ptr() as i32
+    }
+}
+
+// Pointer to a slice in guest memory, as the host expects it
+pub fn slice_ptr<T>(slice: &[T]) -> i32 {
+    slice.as_ptr() as i32
+}
+
+// u1 results come back as i32s
+pub fn to_bool(v: i32) -> bool {
+    v != 0
+}
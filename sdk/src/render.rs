@@ -0,0 +1,384 @@
+// Bindings for the host "render" module
+
+use crate::marshal::{self, CString, Color};
+
+mod raw {
+    #[link(wasm_import_module = "render")]
+    extern "C" {
+        pub fn allocImage() -> i32;
+        pub fn freeImage(id: i32);
+        pub fn updateImage(id: i32, ptr: i32, w: i32, h: i32);
+        pub fn updateImageRegion(id: i32, ptr: i32, w: i32, h: i32, x: i32, y: i32, rw: i32, rh: i32);
+        pub fn markImageDirty(id: i32, ptr: i32, w: i32, h: i32, x: i32, y: i32, rw: i32, rh: i32);
+        pub fn drawImage(id: i32);
+        pub fn drawImageRect(id: i32, x: f32, y: f32, w: f32, h: f32);
+        pub fn drawImageSub(id: i32, x: f32, y: f32, w: f32, h: f32, u: f32, v: f32, uw: f32, vh: f32);
+        pub fn drawImageEx(id: i32, x: f32, y: f32, w: f32, h: f32, u: f32, v: f32, uw: f32, vh: f32,
+            scale: f32, rotation: f32, tint: i32);
+        pub fn drawText(text: i32, x: f32, y: f32, size: f32, color: i32);
+        pub fn measureText(text: i32, size: f32, out: i32);
+        pub fn setCheckerboard(enabled: i32);
+        pub fn fillRect(x: f32, y: f32, w: f32, h: f32, color: i32);
+        pub fn strokeRect(x: f32, y: f32, w: f32, h: f32, width: f32, color: i32);
+        pub fn drawLine(x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: i32);
+        pub fn fillCircle(x: f32, y: f32, radius: f32, color: i32);
+        pub fn strokeCircle(x: f32, y: f32, radius: f32, width: f32, color: i32);
+        pub fn drawPolyline(points: i32, count: i32, width: f32, closed: i32, color: i32);
+        pub fn captureFrame();
+        pub fn allocTarget(w: i32, h: i32) -> i32;
+        pub fn beginTarget(id: i32);
+        pub fn endTarget();
+        pub fn clear(color: i32);
+        pub fn setLayer(layer: i32);
+        pub fn screenWidth() -> i32;
+        pub fn screenHeight() -> i32;
+        pub fn loadImage(path: i32, out: i32) -> i32;
+        pub fn readImage(id: i32, ptr: i32, w: i32, h: i32);
+        pub fn createShader(source: i32) -> i32;
+        pub fn freeShader(id: i32);
+        pub fn setShader(id: i32);
+        pub fn setShaderFloat(id: i32, name: i32, x: f32);
+        pub fn setShaderVec2(id: i32, name: i32, x: f32, y: f32);
+        pub fn setShaderVec3(id: i32, name: i32, x: f32, y: f32, z: f32);
+        pub fn setShaderVec4(id: i32, name: i32, x: f32, y: f32, z: f32, w: f32);
+        pub fn setShaderInt(id: i32, name: i32, x: i32);
+        pub fn addPostPass(id: i32);
+        pub fn setImageSampling(id: i32, mode: i32);
+        pub fn atlasAdd(ptr: i32, w: i32, h: i32) -> i32;
+        pub fn atlasUpdate(id: i32, ptr: i32, w: i32, h: i32);
+        pub fn atlasRemove(id: i32);
+    }
+}
+
+// Why Image::load failed; the host logs the details
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    Unreadable,
+    Unsupported,
+    Corrupt,
+}
+
+// How an image is filtered when it's drawn bigger or smaller than it is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    Nearest = 0,
+    Linear = 1,
+    // Also blends between mip levels when drawn smaller, for zoomed-out views
+    Mipmapped = 2,
+}
+
+// A host image, freed when dropped
+pub struct Image {
+    id: i32,
+}
+impl Image {
+    pub fn alloc() -> Image {
+        Image { id: unsafe { raw::allocImage() } }
+    }
+
+    // Decodes a PNG, JPEG, WebP or BMP file into a new image, returning it with its size
+    pub fn load(path: &str) -> Result<(Image, i32, i32), LoadError> {
+        let path = CString::new(path);
+        let mut out = [0i32; 2];
+        match unsafe { raw::loadImage(path.as_ptr(), out.as_mut_ptr() as i32) } {
+            -1 => Err(LoadError::Unreadable),
+            -2 => Err(LoadError::Unsupported),
+            id if id <= 0 => Err(LoadError::Corrupt),
+            id => Ok((Image { id }, out[0], out[1])),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // Uploads w*h pixels, row-major, bottom row first
+    pub fn update(&self, pixels: &[Color], w: i32, h: i32) {
+        assert_eq!(pixels.len(), (w * h) as usize, "Image::update size mismatch");
+        unsafe { raw::updateImage(self.id, marshal::slice_ptr(pixels), w, h) }
+    }
+
+    pub fn draw(&self) {
+        unsafe { raw::drawImage(self.id) }
+    }
+
+    // Images start out Nearest, and keep their sampling through later updates
+    pub fn set_sampling(&self, sampling: Sampling) {
+        unsafe { raw::setImageSampling(self.id, sampling as i32) }
+    }
+
+    // Uploads just the (x, y, rw, rh) region of a w*h image, which must have
+    // been sized by a full `update` first
+    pub fn update_region(&self, pixels: &[Color], w: i32, h: i32, region: [i32; 4]) {
+        assert_eq!(pixels.len(), (w * h) as usize, "Image::update_region size mismatch");
+        let [x, y, rw, rh] = region;
+        unsafe { raw::updateImageRegion(self.id, marshal::slice_ptr(pixels), w, h, x, y, rw, rh) }
+    }
+
+    // Like `update_region`, but the host merges dirty regions and uploads them
+    // once per frame. `pixels` has to stay put until then.
+    pub fn mark_dirty(&self, pixels: &[Color], w: i32, h: i32, region: [i32; 4]) {
+        assert_eq!(pixels.len(), (w * h) as usize, "Image::mark_dirty size mismatch");
+        let [x, y, rw, rh] = region;
+        unsafe { raw::markImageDirty(self.id, marshal::slice_ptr(pixels), w, h, x, y, rw, rh) }
+    }
+
+    // Positions are in pixels, origin at the bottom-left of the screen
+    pub fn draw_rect(&self, x: f32, y: f32, w: f32, h: f32) {
+        unsafe { raw::drawImageRect(self.id, x, y, w, h) }
+    }
+
+    // Draws the UV sub-region (u, v, uw, vh) of the image into the rect
+    pub fn draw_sub(&self, x: f32, y: f32, w: f32, h: f32, source: [f32; 4]) {
+        let [u, v, uw, vh] = source;
+        unsafe { raw::drawImageSub(self.id, x, y, w, h, u, v, uw, vh) }
+    }
+
+    pub fn draw_ex(&self, x: f32, y: f32, w: f32, h: f32, options: &DrawOptions) {
+        let [u, v, uw, vh] = options.source;
+        unsafe {
+            raw::drawImageEx(self.id, x, y, w, h, u, v, uw, vh,
+                options.scale, options.rotation, options.tint.to_i32())
+        }
+    }
+}
+
+pub struct DrawOptions {
+    // UV sub-region to sample: u, v, width, height
+    pub source: [f32; 4],
+    // Both scale and rotation (radians, counter-clockwise) apply around the rect's center
+    pub scale: f32,
+    pub rotation: f32,
+    pub tint: Color,
+}
+impl Default for DrawOptions {
+    fn default() -> DrawOptions {
+        DrawOptions {
+            source: [0.0, 0.0, 1.0, 1.0],
+            scale: 1.0,
+            rotation: 0.0,
+            tint: Color::rgb(0xff, 0xff, 0xff),
+        }
+    }
+}
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe { raw::freeImage(self.id) }
+    }
+}
+
+// A small image packed into a page shared with others, so they all draw in one
+// batch. Drawn like an Image, but its sampling is the page's. Removed when dropped.
+pub struct AtlasImage {
+    id: i32,
+    w: i32,
+    h: i32,
+}
+impl AtlasImage {
+    // Copies w*h pixels, bottom row first. None if it's too big for a page.
+    pub fn add(pixels: &[Color], w: i32, h: i32) -> Option<AtlasImage> {
+        assert_eq!(pixels.len(), (w * h) as usize, "AtlasImage::add size mismatch");
+        match unsafe { raw::atlasAdd(marshal::slice_ptr(pixels), w, h) } {
+            0 => None,
+            id => Some(AtlasImage { id, w, h }),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // Replaces the pixels, which keep the size they were added with
+    pub fn update(&self, pixels: &[Color]) {
+        assert_eq!(pixels.len(), (self.w * self.h) as usize, "AtlasImage::update size mismatch");
+        unsafe { raw::atlasUpdate(self.id, marshal::slice_ptr(pixels), self.w, self.h) }
+    }
+
+    pub fn draw_rect(&self, x: f32, y: f32, w: f32, h: f32) {
+        unsafe { raw::drawImageRect(self.id, x, y, w, h) }
+    }
+
+    // UVs are within the atlas image, not its page
+    pub fn draw_sub(&self, x: f32, y: f32, w: f32, h: f32, source: [f32; 4]) {
+        let [u, v, uw, vh] = source;
+        unsafe { raw::drawImageSub(self.id, x, y, w, h, u, v, uw, vh) }
+    }
+
+    pub fn draw_ex(&self, x: f32, y: f32, w: f32, h: f32, options: &DrawOptions) {
+        let [u, v, uw, vh] = options.source;
+        unsafe {
+            raw::drawImageEx(self.id, x, y, w, h, u, v, uw, vh,
+                options.scale, options.rotation, options.tint.to_i32())
+        }
+    }
+}
+impl Drop for AtlasImage {
+    fn drop(&mut self) {
+        unsafe { raw::atlasRemove(self.id) }
+    }
+}
+
+// An offscreen image that can be drawn into, then drawn like any other image
+pub struct Target {
+    image: Image,
+}
+impl Target {
+    pub fn alloc(w: i32, h: i32) -> Target {
+        Target { image: Image { id: unsafe { raw::allocTarget(w, h) } } }
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    // Everything drawn inside `f` goes into the target, in its own pixel space
+    pub fn draw_into<F: FnOnce()>(&self, f: F) {
+        unsafe { raw::beginTarget(self.image.id) }
+        f();
+        unsafe { raw::endTarget() }
+    }
+}
+
+// A custom fragment shader, freed when dropped. The source defines
+// `vec4 effect(vec2 uv)`, returning the straight-alpha color at uv, and can
+// call `texel(uv)` to sample the image being drawn.
+pub struct Shader {
+    id: i32,
+}
+impl Shader {
+    // None if it doesn't compile; the host logs why
+    pub fn new(source: &str) -> Option<Shader> {
+        let source = CString::new(source);
+        match unsafe { raw::createShader(source.as_ptr()) } {
+            0 => None,
+            id => Some(Shader { id }),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    // Uniforms keep their values until set again, draws already made keep theirs
+
+    pub fn set_float(&self, name: &str, x: f32) {
+        let name = CString::new(name);
+        unsafe { raw::setShaderFloat(self.id, name.as_ptr(), x) }
+    }
+
+    pub fn set_vec2(&self, name: &str, v: [f32; 2]) {
+        let name = CString::new(name);
+        unsafe { raw::setShaderVec2(self.id, name.as_ptr(), v[0], v[1]) }
+    }
+
+    pub fn set_vec3(&self, name: &str, v: [f32; 3]) {
+        let name = CString::new(name);
+        unsafe { raw::setShaderVec3(self.id, name.as_ptr(), v[0], v[1], v[2]) }
+    }
+
+    pub fn set_vec4(&self, name: &str, v: [f32; 4]) {
+        let name = CString::new(name);
+        unsafe { raw::setShaderVec4(self.id, name.as_ptr(), v[0], v[1], v[2], v[3]) }
+    }
+
+    pub fn set_int(&self, name: &str, x: i32) {
+        let name = CString::new(name);
+        unsafe { raw::setShaderInt(self.id, name.as_ptr(), x) }
+    }
+}
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe { raw::freeShader(self.id) }
+    }
+}
+
+// Draws subsequent images through `shader`, until the end of the frame or
+// until set back to None. Text and shapes aren't affected.
+pub fn set_shader(shader: Option<&Shader>) {
+    unsafe { raw::setShader(shader.map_or(0, |shader| shader.id)) }
+}
+
+// Runs `shader` over the screen (or this component's viewport of it) once the
+// frame's drawn, with `texel` sampling what's there
+pub fn add_post_pass(shader: &Shader) {
+    unsafe { raw::addPostPass(shader.id) }
+}
+
+// Copies a w*h image's pixels into `pixels`, bottom row first. Works on any
+// image id, e.g. one owned by the component asking for a copy.
+pub fn read_image(id: i32, pixels: &mut [Color], w: i32, h: i32) {
+    assert_eq!(pixels.len(), (w * h) as usize, "read_image size mismatch");
+    unsafe { raw::readImage(id, pixels.as_mut_ptr() as i32, w, h) }
+}
+
+// Fills whatever's being drawn to, the screen or a target
+pub fn clear(color: Color) {
+    unsafe { raw::clear(color.to_i32()) }
+}
+
+// Puts the rest of this frame's draws on a layer. Higher layers are drawn on
+// top; within a layer, things are drawn in order.
+pub fn set_layer(layer: i32) {
+    unsafe { raw::setLayer(layer) }
+}
+
+// Draws subsequent images over a transparency checkerboard, until the end of the frame
+pub fn set_checkerboard(enabled: bool) {
+    unsafe { raw::setCheckerboard(enabled as i32) }
+}
+
+// (x, y) is the text's top-left corner, size is the line height in pixels
+pub fn draw_text(text: &str, x: f32, y: f32, size: f32, color: Color) {
+    let text = CString::new(text);
+    unsafe { raw::drawText(text.as_ptr(), x, y, size, color.to_i32()) }
+}
+
+// Width and height in pixels that `draw_text` would cover
+pub fn measure_text(text: &str, size: f32) -> (i32, i32) {
+    let text = CString::new(text);
+    let mut out = [0i32; 2];
+    unsafe { raw::measureText(text.as_ptr(), size, out.as_mut_ptr() as i32) }
+    (out[0], out[1])
+}
+
+// Size of pixel space: the component's viewport, which follows the window.
+// Components exporting `onResize(w, h)` get told when it changes.
+pub fn screen_size() -> (i32, i32) {
+    unsafe { (raw::screenWidth(), raw::screenHeight()) }
+}
+
+// Saves this frame as a numbered screenshot once it's done drawing
+pub fn capture_frame() {
+    unsafe { raw::captureFrame() }
+}
+
+// Shapes are in the same pixel space as images
+
+pub fn fill_rect(x: f32, y: f32, w: f32, h: f32, color: Color) {
+    unsafe { raw::fillRect(x, y, w, h, color.to_i32()) }
+}
+
+// The outline is drawn inside the rect
+pub fn stroke_rect(x: f32, y: f32, w: f32, h: f32, width: f32, color: Color) {
+    unsafe { raw::strokeRect(x, y, w, h, width, color.to_i32()) }
+}
+
+pub fn draw_line(from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+    unsafe { raw::drawLine(from.0, from.1, to.0, to.1, width, color.to_i32()) }
+}
+
+pub fn fill_circle(x: f32, y: f32, radius: f32, color: Color) {
+    unsafe { raw::fillCircle(x, y, radius, color.to_i32()) }
+}
+
+// The ring is centered on the circle's edge
+pub fn stroke_circle(x: f32, y: f32, radius: f32, width: f32, color: Color) {
+    unsafe { raw::strokeCircle(x, y, radius, width, color.to_i32()) }
+}
+
+// Closed polylines also join the last point back to the first
+pub fn draw_polyline(points: &[(f32, f32)], width: f32, closed: bool, color: Color) {
+    unsafe {
+        raw::drawPolyline(marshal::slice_ptr(points), points.len() as i32, width, closed as i32, color.to_i32())
+    }
+}
@@ -0,0 +1,49 @@
+// Bindings for "texture" components, which the host instantiates per Texture
+
+use crate::{marshal::Color, render::Image};
+
+mod raw {
+    #[link(wasm_import_module = "texture")]
+    extern "C" {
+        pub fn _construct() -> i32;
+        pub fn _destroy(id: i32);
+        pub fn init(id: i32, w: i32, h: i32);
+        pub fn loadFrom(id: i32, image: i32, w: i32, h: i32);
+        pub fn getPixel(id: i32, x: i32, y: i32) -> i32;
+        pub fn setPixel(id: i32, x: i32, y: i32, color: i32);
+        pub fn draw(id: i32);
+    }
+}
+
+// Owns a texture component instance, destroyed when dropped
+pub struct Texture {
+    id: i32,
+}
+impl Texture {
+    pub fn new(w: i32, h: i32) -> Texture {
+        let tex = Texture { id: unsafe { raw::_construct() } };
+        unsafe { raw::init(tex.id, w, h) }
+        tex
+    }
+
+    // Replaces the contents with a copy of a w*h image
+    pub fn load_from(&self, image: &Image, w: i32, h: i32) {
+        unsafe { raw::loadFrom(self.id, image.id(), w, h) }
+    }
+
+    pub fn get_pixel(&self, x: i32, y: i32) -> Color {
+        Color::from_i32(unsafe { raw::getPixel(self.id, x, y) })
+    }
+    pub fn set_pixel(&self, x: i32, y: i32, color: Color) {
+        unsafe { raw::setPixel(self.id, x, y, color.to_i32()) }
+    }
+
+    pub fn draw(&self) {
+        unsafe { raw::draw(self.id) }
+    }
+}
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { raw::_destroy(self.id) }
+    }
+}
@@ -0,0 +1,26 @@
+// Bindings for the host "time" module
+
+mod raw {
+    #[link(wasm_import_module = "time")]
+    extern "C" {
+        pub fn elapsed() -> f64;
+        pub fn delta() -> f32;
+        pub fn requestFrame();
+    }
+}
+
+// Seconds since the first frame. Stays put for the whole frame.
+pub fn elapsed() -> f64 {
+    unsafe { raw::elapsed() }
+}
+
+// Seconds since the last `update`, or the fixed step when called from `fixedUpdate`
+pub fn delta() -> f32 {
+    unsafe { raw::delta() }
+}
+
+// Asks for another frame straight away. Frames otherwise wait for input, so
+// anything animating needs to ask every frame.
+pub fn request_frame() {
+    unsafe { raw::requestFrame() }
+}
@@ -0,0 +1,27 @@
+// Packs several wasm modules and their import wiring into one composite component
+// Usage: compose <manifest> <output>
+
+use anyhow::{Result, bail};
+use std::{env, fs};
+
+#[path = "../composite.rs"]
+#[allow(dead_code)]
+mod composite;
+use composite::Composite;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        bail!("Usage: {} <manifest> <output>", args[0]);
+    }
+    let composite = Composite::from_manifest(&args[1])?;
+    for module in &composite.modules {
+        println!("Module {}: {} bytes", module.name, module.wasm.len());
+    }
+    for link in &composite.links {
+        println!("Link {}/{} -> {}", link.importer, link.namespace, link.target);
+    }
+    fs::write(&args[2], composite.to_bytes())?;
+    println!("Wrote {}", args[2]);
+    Ok(())
+}
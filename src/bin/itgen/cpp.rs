@@ -0,0 +1,219 @@
+// Emits C++ glue for an Interface
+
+use std::fmt::Write;
+
+use crate::parser::{Func, Import, Interface, Type};
+
+fn cpp_type(ty: Option<Type>) -> &'static str {
+    match ty {
+        None => "void",
+        Some(Type::U1) => "bool",
+        Some(Type::S8) => "char",
+        Some(Type::U8) => "unsigned char",
+        Some(Type::S32) => "int",
+        Some(Type::U32) => "unsigned",
+        Some(Type::F32) => "float",
+        Some(Type::String) => "const char*",
+    }
+}
+
+// "int _1, int _2"
+fn params(tys: &[Type]) -> String {
+    tys.iter().enumerate()
+        .map(|(i, &ty)| format!("{} _{}", cpp_type(Some(ty)), i + 1))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// "_1, _2"
+fn args(tys: &[Type]) -> String {
+    (1..=tys.len()).map(|i| format!("_{}", i)).collect::<Vec<_>>().join(", ")
+}
+
+pub fn generate(interface: &Interface, source_name: &str, source: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "// Generated by itgen from {}, do not edit", source_name).unwrap();
+    writeln!(out, "#define IMPORT(ns, n) __attribute__((import_module(ns), import_name(n)))").unwrap();
+    writeln!(out, "#define EXPORT(n) __attribute__((export_name(n)))").unwrap();
+    writeln!(out).unwrap();
+    for import in &interface.imports {
+        match &import.resource {
+            None => plain_import(&mut out, import),
+            Some(name) => resource_import(&mut out, import, name),
+        }
+    }
+    for func in &interface.exports {
+        writeln!(out, "EXPORT(\"{}\") {} {}({});",
+            func.name, cpp_type(func.ret), func.name, params(&func.params)).unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "#line 1 \"{}\"", source_name).unwrap();
+    out.push_str(source);
+    out
+}
+
+fn plain_import(out: &mut String, import: &Import) {
+    for func in &import.funcs {
+        writeln!(out, "IMPORT(\"{}\", \"{}\") {} {}({});",
+            import.module, func.name, cpp_type(func.ret), func.name, params(&func.params)).unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+// Resources are host component instances, referred to by handle. The generated
+// class is move-only and destroys its instance when it goes out of scope.
+fn resource_import(out: &mut String, import: &Import, name: &str) {
+    let handle = format!("_{}", name);
+    let module = &import.module;
+    writeln!(out, "using {} = void*;", handle).unwrap();
+    writeln!(out, "IMPORT(\"{}\", \"_construct\") {} {}_construct();", module, handle, name).unwrap();
+    writeln!(out, "IMPORT(\"{}\", \"_destroy\") void {}_destroy({});", module, name, handle).unwrap();
+    for func in &import.funcs {
+        let mut tys = vec![handle.as_str()];
+        tys.extend(func.params.iter().map(|&ty| cpp_type(Some(ty))));
+        writeln!(out, "IMPORT(\"{}\", \"{}\") {} {}_{}({});",
+            module, func.name, cpp_type(func.ret), name, func.name, tys.join(", ")).unwrap();
+    }
+    writeln!(out, "class {} {{", name).unwrap();
+    writeln!(out, "    {} data;", handle).unwrap();
+    writeln!(out, "    bool owned;").unwrap();
+    writeln!(out, "public:").unwrap();
+    writeln!(out, "    {}() : data({}_construct()), owned(true) {{}}", name, name).unwrap();
+    writeln!(out, "    ~{}() {{", name).unwrap();
+    writeln!(out, "        if (owned) {}_destroy(data);", name).unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "    {}(const {}&) = delete;", name, name).unwrap();
+    writeln!(out, "    {}& operator=(const {}&) = delete;", name, name).unwrap();
+    writeln!(out, "    {}({}&& other) : data(other.data), owned(other.owned) {{", name, name).unwrap();
+    writeln!(out, "        other.owned = false;").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "    {}& operator=({}&& other) {{", name, name).unwrap();
+    writeln!(out, "        if (owned) {}_destroy(data);", name).unwrap();
+    writeln!(out, "        data = other.data;").unwrap();
+    writeln!(out, "        owned = other.owned;").unwrap();
+    writeln!(out, "        other.owned = false;").unwrap();
+    writeln!(out, "        return *this;").unwrap();
+    writeln!(out, "    }}").unwrap();
+    for func in &import.funcs {
+        method(out, name, func);
+    }
+    writeln!(out, "}};").unwrap();
+    writeln!(out).unwrap();
+}
+
+fn method(out: &mut String, name: &str, func: &Func) {
+    let call_args = if func.params.is_empty() {
+        "data".to_string()
+    } else {
+        format!("data, {}", args(&func.params))
+    };
+    writeln!(out, "    {} {}({}) {{", cpp_type(func.ret), func.name, params(&func.params)).unwrap();
+    writeln!(out, "        return {}_{}({});", name, func.name, call_args).unwrap();
+    writeln!(out, "    }}").unwrap();
+}
+
+// The .itl sidecar: the interface in canonical IT syntax, aliases resolved
+pub fn itl(interface: &Interface) -> String {
+    let mut out = String::new();
+    let func_list = |out: &mut String, funcs: &[Func]| {
+        for func in funcs {
+            let tys = func.params.iter().map(|ty| ty.name()).collect::<Vec<_>>().join(", ");
+            match func.ret {
+                Some(ret) => writeln!(out, "    func {}({}) -> {};", func.name, tys, ret.name()).unwrap(),
+                None => writeln!(out, "    func {}({});", func.name, tys).unwrap(),
+            }
+        }
+    };
+    for import in &interface.imports {
+        match &import.resource {
+            Some(name) => writeln!(out, "type {} = import \"{}\" {{", name, import.module).unwrap(),
+            None => writeln!(out, "import \"{}\" {{", import.module).unwrap(),
+        }
+        func_list(&mut out, &import.funcs);
+        writeln!(out, "}}").unwrap();
+    }
+    writeln!(out, "export {{").unwrap();
+    func_list(&mut out, &interface.exports);
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+
+    const BLOCK: &str = r#"
+import "render" {
+    func allocImage() -> s32;
+    func drawImage(s32);
+}
+type Texture = import "texture" {
+    func getPixel(s32, s32) -> s32;
+    func clear();
+}
+export {
+    func init(s32, s32);
+    func name() -> string;
+}
+"#;
+
+    const GOLDEN_CPP: &str = r#"// Generated by itgen from small.cpp, do not edit
+#define IMPORT(ns, n) __attribute__((import_module(ns), import_name(n)))
+#define EXPORT(n) __attribute__((export_name(n)))
+
+IMPORT("render", "allocImage") int allocImage();
+IMPORT("render", "drawImage") void drawImage(int _1);
+
+using _Texture = void*;
+IMPORT("texture", "_construct") _Texture Texture_construct();
+IMPORT("texture", "_destroy") void Texture_destroy(_Texture);
+IMPORT("texture", "getPixel") int Texture_getPixel(_Texture, int, int);
+IMPORT("texture", "clear") void Texture_clear(_Texture);
+class Texture {
+    _Texture data;
+    bool owned;
+public:
+    Texture() : data(Texture_construct()), owned(true) {}
+    ~Texture() {
+        if (owned) Texture_destroy(data);
+    }
+    Texture(const Texture&) = delete;
+    Texture& operator=(const Texture&) = delete;
+    Texture(Texture&& other) : data(other.data), owned(other.owned) {
+        other.owned = false;
+    }
+    Texture& operator=(Texture&& other) {
+        if (owned) Texture_destroy(data);
+        data = other.data;
+        owned = other.owned;
+        other.owned = false;
+        return *this;
+    }
+    int getPixel(int _1, int _2) {
+        return Texture_getPixel(data, _1, _2);
+    }
+    void clear() {
+        return Texture_clear(data);
+    }
+};
+
+EXPORT("init") void init(int _1, int _2);
+EXPORT("name") const char* name();
+
+#line 1 "small.cpp"
+int x;
+"#;
+
+    #[test]
+    fn generates_cpp() {
+        let interface = parser::parse(BLOCK, 1).unwrap();
+        assert_eq!(generate(&interface, "small.cpp", "int x;\n"), GOLDEN_CPP);
+    }
+
+    #[test]
+    fn itl_is_the_canonical_block() {
+        let interface = parser::parse(BLOCK, 1).unwrap();
+        assert_eq!(itl(&interface), &BLOCK[1..]);
+    }
+}
@@ -0,0 +1,41 @@
+// Generates C++ import/export glue from a module's IT block
+// Usage: itgen <source.cpp> --cpp <out.cpp> --itl <out.itl>
+
+use anyhow::{Result, bail, format_err};
+use std::{env, fs};
+
+mod cpp;
+mod parser;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let usage = format!("Usage: {} <source.cpp> --cpp <out.cpp> --itl <out.itl>", args[0]);
+    let mut source_path = None;
+    let mut cpp_path = None;
+    let mut itl_path = None;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--cpp" => cpp_path = iter.next(),
+            "--itl" => itl_path = iter.next(),
+            _ if source_path.is_none() => source_path = Some(arg),
+            _ => bail!("{}", usage),
+        }
+    }
+    let source_path = source_path.ok_or(format_err!("{}", usage))?;
+
+    let source = fs::read_to_string(source_path)?;
+    let (block, line) = parser::extract_block(&source)
+        .map_err(|e| format_err!("{}: {}", source_path, e))?;
+    let interface = parser::parse(block, line)
+        .map_err(|e| format_err!("{}: {}", source_path, e))?;
+
+    if let Some(path) = cpp_path {
+        let body = parser::strip_block(&source)?;
+        fs::write(path, cpp::generate(&interface, source_path, &body))?;
+    }
+    if let Some(path) = itl_path {
+        fs::write(path, cpp::itl(&interface))?;
+    }
+    Ok(())
+}
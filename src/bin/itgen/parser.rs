@@ -0,0 +1,363 @@
+// Parses the IT block out of a module source file
+
+use anyhow::{Result, bail, format_err};
+
+const IT_START: &str = "/**IT_START**/";
+const IT_END: &str = "/**IT_END**/";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Type {
+    U1,
+    S8,
+    U8,
+    S32,
+    U32,
+    F32,
+    String,
+}
+impl Type {
+    fn from_name(name: &str) -> Option<Type> {
+        Some(match name {
+            "u1" => Type::U1,
+            "s8" => Type::S8,
+            "u8" => Type::U8,
+            "s32" => Type::S32,
+            "u32" => Type::U32,
+            "f32" => Type::F32,
+            "string" => Type::String,
+            _ => return None,
+        })
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::U1 => "u1",
+            Type::S8 => "s8",
+            Type::U8 => "u8",
+            Type::S32 => "s32",
+            Type::U32 => "u32",
+            Type::F32 => "f32",
+            Type::String => "string",
+        }
+    }
+}
+
+pub struct Func {
+    pub name: String,
+    pub params: Vec<Type>,
+    pub ret: Option<Type>,
+}
+
+pub struct Import {
+    pub module: String,
+    // Set for `type Name = import "module" {...}`, which imports a resource
+    pub resource: Option<String>,
+    pub funcs: Vec<Func>,
+}
+
+pub struct Interface {
+    pub imports: Vec<Import>,
+    pub exports: Vec<Func>,
+}
+
+// Returns the IT block's contents and the line it starts on
+pub fn extract_block(source: &str) -> Result<(&str, usize)> {
+    let start = source.find(IT_START).ok_or(format_err!("Missing {}", IT_START))?;
+    let body_start = start + IT_START.len();
+    let end = source[body_start..].find(IT_END).ok_or(format_err!("Missing {}", IT_END))?;
+    let line = source[..body_start].lines().count();
+    Ok((&source[body_start..body_start + end], line))
+}
+
+// The source with its IT block blanked out, keeping line numbers intact
+pub fn strip_block(source: &str) -> Result<String> {
+    let (block, _) = extract_block(source)?;
+    let start = block.as_ptr() as usize - source.as_ptr() as usize;
+    let end = start + block.len();
+    let newlines = "\n".repeat(block.matches('\n').count());
+    Ok(format!("{}{}{}", &source[..start], newlines, &source[end..]))
+}
+
+pub fn parse(block: &str, first_line: usize) -> Result<Interface> {
+    let tokens = tokenize(block, first_line)?;
+    let mut parser = Parser { tokens, pos: 0, aliases: Vec::new() };
+    parser.interface()
+}
+
+#[derive(Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Punct(&'static str),
+}
+
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+fn tokenize(text: &str, first_line: usize) -> Result<Vec<Token>> {
+    const PUNCTS: &[&str] = &["->", "{", "}", "(", ")", ";", ",", "=", ":"];
+    let mut tokens = Vec::new();
+    let mut line = first_line;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+        } else if rest.starts_with("/*") {
+            let end = rest.find("*/").ok_or(format_err!("line {}: Unterminated comment", line))?;
+            line += rest[..end].matches('\n').count();
+            rest = &rest[end + 2..];
+        } else if c == '"' {
+            let end = rest[1..].find('"').ok_or(format_err!("line {}: Unterminated string", line))?;
+            tokens.push(Token { tok: Tok::Str(rest[1..end + 1].to_string()), line });
+            rest = &rest[end + 2..];
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            tokens.push(Token { tok: Tok::Ident(rest[..end].to_string()), line });
+            rest = &rest[end..];
+        } else if let Some(p) = PUNCTS.iter().find(|p| rest.starts_with(*p)) {
+            tokens.push(Token { tok: Tok::Punct(p), line });
+            rest = &rest[p.len()..];
+        } else {
+            bail!("line {}: Unexpected character: {}", line, c);
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // `type Name = <type>;` declarations
+    aliases: Vec<(String, Type)>,
+}
+impl Parser {
+    fn interface(&mut self) -> Result<Interface> {
+        let mut imports = Vec::new();
+        let mut exports = Vec::new();
+        while self.pos < self.tokens.len() {
+            let keyword = self.ident()?;
+            match keyword.as_str() {
+                "import" => {
+                    let module = self.string()?;
+                    let funcs = self.func_list()?;
+                    imports.push(Import { module, resource: None, funcs });
+                },
+                "export" => {
+                    exports.extend(self.func_list()?);
+                },
+                "type" => {
+                    let name = self.ident()?;
+                    self.expect("=")?;
+                    if self.peek_ident("import") {
+                        self.pos += 1;
+                        let module = self.string()?;
+                        let funcs = self.func_list()?;
+                        imports.push(Import { module, resource: Some(name), funcs });
+                    } else {
+                        let ty = self.ty()?;
+                        self.expect(";")?;
+                        self.aliases.push((name, ty));
+                    }
+                },
+                _ => return Err(self.error(&format!("Expected import, export or type, found {}", keyword))),
+            }
+        }
+        Ok(Interface { imports, exports })
+    }
+
+    fn func_list(&mut self) -> Result<Vec<Func>> {
+        self.expect("{")?;
+        let mut funcs = Vec::new();
+        while !self.peek_punct("}") {
+            funcs.push(self.func()?);
+        }
+        self.expect("}")?;
+        Ok(funcs)
+    }
+
+    fn func(&mut self) -> Result<Func> {
+        let keyword = self.ident()?;
+        if keyword != "func" {
+            return Err(self.error(&format!("Expected func, found {}", keyword)));
+        }
+        let name = self.ident()?;
+        self.expect("(")?;
+        let mut params = Vec::new();
+        while !self.peek_punct(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            // Parameter names are optional: `func f(x: s32)` or `func f(s32)`
+            if self.tokens.get(self.pos + 1).map_or(false, |t| t.tok == Tok::Punct(":")) {
+                self.pos += 2;
+            }
+            params.push(self.ty()?);
+        }
+        self.expect(")")?;
+        let ret = if self.peek_punct("->") {
+            self.pos += 1;
+            Some(self.ty()?)
+        } else {
+            None
+        };
+        self.expect(";")?;
+        Ok(Func { name, params, ret })
+    }
+
+    fn ty(&mut self) -> Result<Type> {
+        let name = self.ident()?;
+        Type::from_name(&name)
+            .or_else(|| self.aliases.iter().find(|(alias, _)| *alias == name).map(|(_, ty)| *ty))
+            .ok_or_else(|| self.error(&format!("Unknown type: {}", name)))
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Tok::Ident(s) => Ok(s),
+            _ => Err(self.error("Expected identifier")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.next()? {
+            Tok::Str(s) => Ok(s),
+            _ => Err(self.error("Expected string")),
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        match self.next()? {
+            Tok::Punct(p) if p == punct => Ok(()),
+            _ => Err(self.error(&format!("Expected '{}'", punct))),
+        }
+    }
+
+    fn next(&mut self) -> Result<Tok> {
+        if self.pos >= self.tokens.len() {
+            bail!("Unexpected end of IT block");
+        }
+        let tok = self.tokens[self.pos].tok.clone();
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn peek_punct(&self, punct: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token { tok: Tok::Punct(p), .. }) if *p == punct)
+    }
+
+    fn peek_ident(&self, ident: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token { tok: Tok::Ident(s), .. }) if s == ident)
+    }
+
+    // Reports at the most recently consumed token
+    fn error(&self, msg: &str) -> anyhow::Error {
+        let line = self.tokens.get(self.pos.saturating_sub(1)).map_or(0, |t| t.line);
+        format_err!("line {}: {}", line, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "name(s32, f32) -> u1"
+    fn signature(func: &Func) -> String {
+        let params = func.params.iter().map(|ty| ty.name()).collect::<Vec<_>>().join(", ");
+        match func.ret {
+            Some(ret) => format!("{}({}) -> {}", func.name, params, ret.name()),
+            None => format!("{}({})", func.name, params),
+        }
+    }
+
+    fn signatures(funcs: &[Func]) -> Vec<String> {
+        funcs.iter().map(signature).collect()
+    }
+
+    fn error(block: &str) -> String {
+        parse(block, 10).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_imports_and_exports() {
+        let interface = parse(r#"
+            import "render" {
+                func allocImage() -> s32;
+                func drawImage(id: s32, scale: f32);
+            }
+            import "env" {}
+            export {
+                func init(s32, s32);
+                func name() -> string;
+            }
+            export {
+                func draw();
+            }
+        "#, 1).unwrap();
+        assert_eq!(interface.imports.len(), 2);
+        assert_eq!(interface.imports[0].module, "render");
+        assert!(interface.imports[0].resource.is_none());
+        assert_eq!(signatures(&interface.imports[0].funcs), ["allocImage() -> s32", "drawImage(s32, f32)"]);
+        assert_eq!(interface.imports[1].module, "env");
+        assert!(interface.imports[1].funcs.is_empty());
+        // Export blocks add up
+        assert_eq!(signatures(&interface.exports), ["init(s32, s32)", "name() -> string", "draw()"]);
+    }
+
+    #[test]
+    fn parses_types() {
+        let interface = parse(r#"
+            type Color = u32;
+            type Texture = import "texture" {
+                func getPixel(s32, s32) -> Color;
+                func setPixel(x: s32, y: s32, color: Color);
+            }
+            export {
+                func blend(Color, Color, u8, s8, u1) -> Color;
+            }
+        "#, 1).unwrap();
+        assert_eq!(interface.imports.len(), 1);
+        let texture = &interface.imports[0];
+        assert_eq!((texture.module.as_str(), texture.resource.as_deref()), ("texture", Some("Texture")));
+        // Aliases resolve to what they name
+        assert_eq!(signatures(&texture.funcs), ["getPixel(s32, s32) -> u32", "setPixel(s32, s32, u32)"]);
+        assert_eq!(signatures(&interface.exports), ["blend(u32, u32, u8, s8, u1) -> u32"]);
+    }
+
+    #[test]
+    fn skips_comments() {
+        let interface = parse("// import \"hidden\" {}\nexport { /* func hidden();\n */ func shown(); } // trailing", 1).unwrap();
+        assert!(interface.imports.is_empty());
+        assert_eq!(signatures(&interface.exports), ["shown()"]);
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        assert_eq!(error("import \"render\" {\n    func f(s33);\n}"), "line 11: Unknown type: s33");
+        assert_eq!(error("/* one\ntwo */\n\nexport { func f() }"), "line 13: Expected ';'");
+        assert_eq!(error("\nexports {}"), "line 11: Expected import, export or type, found exports");
+        assert_eq!(error("export {\n    fn f();\n}"), "line 11: Expected func, found fn");
+        assert_eq!(error("import render {}"), "line 10: Expected string");
+        assert_eq!(error("export { func f(s32 s32); }"), "line 10: Expected ','");
+        assert_eq!(error("\n\nexport { func f() # }"), "line 12: Unexpected character: #");
+        assert_eq!(error("\n/* open"), "line 11: Unterminated comment");
+        assert_eq!(error("import \"render"), "line 10: Unterminated string");
+        assert_eq!(error("export {"), "Unexpected end of IT block");
+    }
+
+    #[test]
+    fn finds_the_block() {
+        let source = "#include <x>\n/**IT_START**/\nexport {\n}\n/**IT_END**/\nint main() {}\n";
+        let (block, line) = extract_block(source).unwrap();
+        assert_eq!((block, line), ("\nexport {\n}\n", 2));
+        // Blanked out, code after it stays on the same line
+        assert_eq!(strip_block(source).unwrap(), "#include <x>\n/**IT_START**/\n\n\n/**IT_END**/\nint main() {}\n");
+        assert_eq!(extract_block("/**IT_START**/").err().unwrap().to_string(), "Missing /**IT_END**/");
+        assert_eq!(extract_block("").err().unwrap().to_string(), "Missing /**IT_START**/");
+    }
+}
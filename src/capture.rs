@@ -0,0 +1,79 @@
+// Saving rendered frames as PNGs: one-off screenshots, and numbered frame
+// sequences for recordings. Also where render command dumps go.
+
+use anyhow::Result;
+use std::{
+    cell::Cell,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+pub const CAPTURE_DIR: &str = "captures";
+
+thread_local! {
+    // Where the search for an unused screenshot number starts. Advanced per request,
+    // since the file isn't written until the frame is read back.
+    static NEXT_SCREENSHOT: Cell<u32> = Cell::new(1);
+}
+
+// The first unused captures/screenshot-NNNN.png not already handed out
+pub fn screenshot_path() -> PathBuf {
+    NEXT_SCREENSHOT.with(|next| {
+        let (i, path) = (next.get()..)
+            .map(|i| (i, Path::new(CAPTURE_DIR).join(format!("screenshot-{:04}.png", i))))
+            .find(|(_, path)| !path.exists())
+            .unwrap();
+        next.set(i + 1);
+        path
+    })
+}
+
+// Frames of a recording, numbered in order so tools can pick them up as a sequence
+pub fn frame_path(index: u64) -> PathBuf {
+    Path::new(CAPTURE_DIR).join("frames").join(format!("frame-{:06}.png", index))
+}
+
+// Text dumps of each frame's render commands, numbered like frames
+pub fn commands_path(index: u64) -> PathBuf {
+    Path::new(CAPTURE_DIR).join("commands").join(format!("frame-{:06}.txt", index))
+}
+
+pub fn write_text(path: &Path, text: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+// `pixels` is a framebuffer readback: RGBA, rows bottom-up. Alpha is dropped,
+// the framebuffer is opaque anyway.
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for row in pixels.chunks_exact(width as usize * 4).rev() {
+        for pixel in row.chunks_exact(4) {
+            rgb.extend_from_slice(&pixel[0..3]);
+        }
+    }
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&rgb)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshots_in_one_frame_get_distinct_paths() {
+        let first = screenshot_path();
+        let second = screenshot_path();
+        assert_ne!(first, second);
+    }
+}
@@ -4,12 +4,18 @@ use anyhow::{Result, anyhow, format_err};
 use std::{
     cell::RefCell,
     collections::HashMap,
+    fs,
     rc::Rc,
 };
 
 use wasmtime::*;
 
-static mut COMPONENTS: Vec<Rc<RefCell<Component>>> = Vec::new();
+use crate::composite::Composite;
+use crate::emscripten;
+use crate::renderer;
+
+// Slots are None'd out by _destroy and reused by the next _construct
+static mut COMPONENTS: Vec<Option<Rc<RefCell<Component>>>> = Vec::new();
 pub struct WrappedComponent {}
 impl<'a> WrappedComponent {
     pub fn loader<T>(store: &Store, imports: T) -> ImportModule
@@ -21,54 +27,94 @@ impl<'a> WrappedComponent {
         {
             // let rc = COMPONENTS.clone();
             let s2 = store.clone();
-            module.add_func("_construct", Func::wrap(&store.clone(), move || {
+            module.add_func("_construct", Func::wrap(&store.clone(), move || -> Result<i32, Trap> {
                 unsafe {
-                    let id = COMPONENTS.len();
                     let texture_rc = Component::init(&s2);
-                    let instance = Component::initialize(&texture_rc, "modules/out/texture.wasm", imports(&texture_rc)).unwrap();
+                    let instance = Component::initialize(&texture_rc, "modules/out/texture.wasm", imports(&texture_rc))
+                        .map_err(to_trap)?;
                     texture_rc.borrow_mut().instance = Some(instance);
-                    COMPONENTS.push(texture_rc);
-                    id as i32
+                    let id = match COMPONENTS.iter().position(|slot| slot.is_none()) {
+                        Some(id) => { COMPONENTS[id] = Some(texture_rc); id }
+                        None => { COMPONENTS.push(Some(texture_rc)); COMPONENTS.len() - 1 }
+                    };
+                    Ok(id as i32)
                 }
             }));
         }
-        module.add_func("init", Func::wrap(&store, |id: i32, w, h| {
-            unsafe {
-                COMPONENTS[id as usize].borrow().get_func("init").unwrap()
-                    .get2::<i32, i32, ()>().unwrap()
-                    (w, h).unwrap();
+        module.add_func("_destroy", Func::wrap(&store, |id: i32| -> Result<(), Trap> {
+            let component_rc = unsafe {
+                COMPONENTS.get_mut(id as usize).and_then(|slot| slot.take())
+            }.ok_or_else(|| Trap::new(format!("_destroy called on dead component id: {}", id)))?;
+            // Give the guest a chance to free its own resources; deinit is optional
+            let deinit = component_rc.borrow().get_func("deinit");
+            if let Ok(deinit) = deinit {
+                deinit.get0::<()>().map_err(to_trap)?()?;
             }
+            // Anything the guest didn't free gets cleaned up when the last Rc drops here
+            Ok(())
         }));
-        module.add_func("setPixel", Func::wrap(&store, |id: i32, x, y, color| {
-            unsafe {
-                COMPONENTS[id as usize].borrow().get_func("setPixel").unwrap()
-                    .get3::<i32, i32, i32, ()>().unwrap()
-                    (x, y, color).unwrap();
-            }
+        module.add_func("init", Func::wrap(&store, |id: i32, w, h| -> Result<(), Trap> {
+            WrappedComponent::get_func(id, "init")?
+                .get2::<i32, i32, ()>().map_err(to_trap)?
+                (w, h)
         }));
-        module.add_func("getPixel", Func::wrap(&store, |id: i32, x, y| {
-            unsafe {
-                COMPONENTS[id as usize].borrow().get_func("getPixel").unwrap()
-                    .get2::<i32, i32, i32>().unwrap()
-                    (x, y).unwrap()
-            }
+        module.add_func("loadFrom", Func::wrap(&store, |id: i32, image, w, h| -> Result<(), Trap> {
+            WrappedComponent::get_func(id, "loadFrom")?
+                .get3::<i32, i32, i32, ()>().map_err(to_trap)?
+                (image, w, h)
         }));
-        module.add_func("draw", Func::wrap(&store, |id: i32| {
-            unsafe {
-                COMPONENTS[id as usize].borrow().get_func("draw").unwrap()
-                    .get0::<()>().unwrap()
-                    ().unwrap()
-            }
+        module.add_func("setPixel", Func::wrap(&store, |id: i32, x, y, color| -> Result<(), Trap> {
+            WrappedComponent::get_func(id, "setPixel")?
+                .get3::<i32, i32, i32, ()>().map_err(to_trap)?
+                (x, y, color)
+        }));
+        module.add_func("getPixel", Func::wrap(&store, |id: i32, x, y| -> Result<i32, Trap> {
+            WrappedComponent::get_func(id, "getPixel")?
+                .get2::<i32, i32, i32>().map_err(to_trap)?
+                (x, y)
+        }));
+        module.add_func("draw", Func::wrap(&store, |id: i32| -> Result<(), Trap> {
+            WrappedComponent::get_func(id, "draw")?
+                .get0::<()>().map_err(to_trap)?
+                ()
         }));
 
         module
     }
+
+    // Looks up an export on a live wrapped component. The borrow is released before
+    // returning, so the callee is free to call imports that mutate its Component.
+    fn get_func(id: i32, name: &str) -> Result<Func, Trap> {
+        let component_rc = unsafe {
+            COMPONENTS.get(id as usize).and_then(|slot| slot.clone())
+        }.ok_or_else(|| Trap::new(format!("Use of dead component id: {}", id)))?;
+        let func = component_rc.borrow().get_func(name).map_err(to_trap);
+        func
+    }
+
+    // Reports any wrapped components that were never _destroy'd, then frees them
+    pub fn report_leaks() {
+        let leaked = unsafe { std::mem::replace(&mut COMPONENTS, Vec::new()) };
+        for (id, slot) in leaked.into_iter().enumerate() {
+            if let Some(component_rc) = slot {
+                let component = component_rc.borrow();
+                println!("Leak: component {} (id {}) was never destroyed, owns {} image(s)",
+                    component.filename, id, component.images.len());
+            }
+        }
+    }
 }
 
 pub struct Component {
     filename: String,
     pub instance: Option<Instance>,
+    // Sub-instances of a composite component, kept alive alongside the main one
+    linked: Vec<Instance>,
     pub store: Store,
+    // GPU resources owned by this component, freed on drop
+    pub images: Vec<u32>,
+    pub shaders: Vec<u32>,
+    pub atlas_images: Vec<u32>,
 }
 impl Component {
     pub fn init(store: &Store) -> Rc<RefCell<Component>> {
@@ -76,6 +122,10 @@ impl Component {
             filename: String::new(),
             store: store.clone(),
             instance: None,
+            linked: Vec::new(),
+            images: Vec::new(),
+            shaders: Vec::new(),
+            atlas_images: Vec::new(),
         }))
     }
 
@@ -83,24 +133,115 @@ impl Component {
         println!("Compiling module: {}", filename);
         // Store filename for later
         { component.borrow_mut().filename = filename.to_string(); }
-        let store = &component.borrow().store;
-        let module = Module::from_file(store, filename)?;
+        let store = component.borrow().store.clone();
+        let bytes = fs::read(filename)?;
 
-        // Hack simple wasi syscalls in until we can polyfill around them via composite components
-        imports.add_module("wasi_snapshot_preview1", ImportModule::from_vec(vec![
-            ("proc_exit", Func::wrap(&store, |code: i32| { panic!("wasi proc_exit called w/ code: {}", code); })),
-        ]));
+        // Fill in the emscripten runtime around whatever the caller provided
+        imports.add_fallback("env", emscripten::env_module(component));
+        imports.add_fallback("wasi_snapshot_preview1", emscripten::wasi_module(component));
 
+        if Composite::is_composite(&bytes) {
+            let composite = Composite::from_bytes(&bytes)?;
+            return Component::initialize_composite(component, &composite, &imports);
+        }
+
+        let module = Module::new(&store, &bytes)?;
         println!("Instantiating module...");
         Instance::new(&module, &imports.to_extern_list(&module)?)
     }
 
+    // Instantiates each sub-module in order. Imports linked to another sub-module are
+    // bound here; function links go through trampolines so modules can call each
+    // other in both directions, non-function links need their provider listed first.
+    fn initialize_composite(component: &Rc<RefCell<Component>>, composite: &Composite, imports: &Imports) -> Result<Instance> {
+        let store = component.borrow().store.clone();
+        let mut instances: HashMap<String, Instance> = HashMap::new();
+        let mut pending: Vec<(Rc<RefCell<Option<Func>>>, String, String)> = Vec::new();
+        for sub in &composite.modules {
+            println!("Instantiating composite member: {}", sub.name);
+            let module = Module::new(&store, &sub.wasm)?;
+            let mut externs = Vec::new();
+            for import in module.imports() {
+                let (mod_name, name) = (import.module(), import.name());
+                let target = match composite.link_target(&sub.name, mod_name) {
+                    Some(target) => target,
+                    None => {
+                        externs.push(imports.get_extern(mod_name, name)?);
+                        continue;
+                    }
+                };
+                match import.ty() {
+                    ExternType::Func(ty) => {
+                        let cell = Rc::new(RefCell::new(None));
+                        pending.push((cell.clone(), target.to_string(), name.to_string()));
+                        externs.push(link_trampoline(&store, ty.clone(), cell, format!("{}/{}", target, name)).into());
+                    },
+                    _ => {
+                        let export = instances.get(target)
+                            .and_then(|instance| instance.get_export(name))
+                            .ok_or(format_err!("Link not satisfied: {}/{}/{}", sub.name, mod_name, name))?;
+                        externs.push(export);
+                    },
+                }
+            }
+            instances.insert(sub.name.clone(), Instance::new(&module, &externs)?);
+        }
+        for (cell, target, name) in pending {
+            let func = instances[&target].get_func(&name)
+                .ok_or(format_err!("Link not satisfied: {}/{}", target, name))?;
+            *cell.borrow_mut() = Some(func);
+        }
+        let main = instances.remove(&composite.export)
+            .ok_or(format_err!("Composite export module missing: {}", composite.export))?;
+        component.borrow_mut().linked = instances.into_iter().map(|(_, instance)| instance).collect();
+        Ok(main)
+    }
+
+    // Throws away the current instance and everything it owns, then instantiates
+    // the same file again with a fresh set of imports
+    pub fn reload(component: &Rc<RefCell<Component>>, imports: Imports) -> Result<()> {
+        let filename = {
+            let mut component = component.borrow_mut();
+            component.instance = None;
+            component.linked.clear();
+            component.release_resources();
+            component.filename.clone()
+        };
+        let instance = Component::initialize(component, &filename, imports)?;
+        component.borrow_mut().instance = Some(instance);
+        Ok(())
+    }
+
+    fn release_resources(&mut self) {
+        if !self.images.is_empty() {
+            println!("Freeing {} image(s) still owned by {}", self.images.len(), self.filename);
+            renderer::free_images(&self.images);
+            self.images.clear();
+        }
+        if !self.shaders.is_empty() {
+            println!("Freeing {} shader(s) still owned by {}", self.shaders.len(), self.filename);
+            renderer::free_shaders(&self.shaders);
+            self.shaders.clear();
+        }
+        if !self.atlas_images.is_empty() {
+            println!("Freeing {} atlas image(s) still owned by {}", self.atlas_images.len(), self.filename);
+            renderer::free_atlas_images(&self.atlas_images);
+            self.atlas_images.clear();
+        }
+    }
+
     pub fn get_func(&self, name: &str) -> Result<Func> {
         let instance = self.instance.as_ref().ok_or(anyhow!("Instance not set"))?;
         let f = instance.get_func(name).ok_or(format_err!("Failed to find function: {} in component {}", name, self.filename))?;
         Ok(f)
     }
 
+    // Traps rather than panics, since imports can be called during instantiation
+    pub fn memory(&self) -> Result<Memory, Trap> {
+        self.instance.as_ref().and_then(|instance| instance.get_memory("memory"))
+            .ok_or_else(|| Trap::new(format!("{} has no memory export", self.filename)))
+    }
+
     pub fn get_exports(&self) -> ImportModule {
         let instance = self.instance.as_ref().unwrap();
         let mut exports = ImportModule::new();
@@ -112,6 +253,11 @@ impl Component {
         exports
     }
 }
+impl Drop for Component {
+    fn drop(&mut self) {
+        self.release_resources();
+    }
+}
 
 // An import dictionary
 pub struct Imports {
@@ -134,41 +280,67 @@ impl Imports {
         self.modules.insert(name.to_string(), module);
     }
 
+    // Adds the functions from `module` that aren't already provided under `name`
+    pub fn add_fallback(&mut self, name: &str, module: ImportModule) {
+        let cur = self.modules.entry(name.to_string()).or_insert_with(ImportModule::new);
+        for (func_name, ext) in module.externs {
+            cur.externs.entry(func_name).or_insert(ext);
+        }
+    }
+
     fn to_extern_list(&self, module: &Module) -> Result<Vec<Extern>> {
         let mut imports = Vec::new();
         for import in module.imports() {
-            let mod_name = import.module();
-            let cur = self.modules.get(import.module())
-                .ok_or(format_err!("No module found with name: {}", mod_name))?;
-            let func_name = import.name();
-            let func = cur.funcs.get(import.name())
-                .ok_or(format_err!("Import not found: {}/{}", mod_name, func_name))?;
-            imports.push(func.clone().into());
+            imports.push(self.get_extern(import.module(), import.name())?);
         }
         Ok(imports)
     }
+
+    fn get_extern(&self, mod_name: &str, name: &str) -> Result<Extern> {
+        let cur = self.modules.get(mod_name)
+            .ok_or(format_err!("No module found with name: {}", mod_name))?;
+        let ext = cur.externs.get(name)
+            .ok_or(format_err!("Import not found: {}/{}", mod_name, name))?;
+        Ok(ext.clone())
+    }
 }
 
 // A set of imports for one module in an import dictionary
 pub struct ImportModule {
-    funcs: HashMap<String, Func>,
+    externs: HashMap<String, Extern>,
 }
 impl ImportModule {
     pub fn new() -> ImportModule {
         ImportModule {
-            funcs: HashMap::new(),
+            externs: HashMap::new(),
         }
     }
 
     pub fn from_vec(list: Vec<(&str, Func)>) -> ImportModule {
-        let mut funcs = HashMap::new();
+        let mut externs = HashMap::new();
         for (name, func) in list {
-            funcs.insert(name.to_string(), func);
+            externs.insert(name.to_string(), func.into());
         }
-        ImportModule { funcs }
+        ImportModule { externs }
     }
 
     pub fn add_func(&mut self, name: &str, f: Func) {
-        self.funcs.insert(name.to_string(), f);
+        self.externs.insert(name.to_string(), f.into());
     }
 }
+
+// Forwards calls to a function that gets filled in once its instance exists
+// Host errors inside an import surface to the calling guest as traps
+fn to_trap(e: anyhow::Error) -> Trap {
+    Trap::new(e.to_string())
+}
+
+fn link_trampoline(store: &Store, ty: FuncType, target: Rc<RefCell<Option<Func>>>, name: String) -> Func {
+    Func::new(store, ty, move |_caller, params, results| {
+        let func = target.borrow().clone()
+            .ok_or_else(|| Trap::new(format!("Linked function {} called before composite finished loading", name)))?;
+        let values = func.call(params).map_err(|e| Trap::new(e.to_string()))?;
+        results.clone_from_slice(&values);
+        Ok(())
+    })
+}
@@ -0,0 +1,358 @@
+// Composite components: several wasm modules plus the wiring between them,
+// packed into a single artifact that the host loads as one Component
+
+use anyhow::{Result, anyhow, bail, format_err};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+use wasmparser::{ExternalKind, ImportSectionEntryType, ModuleReader, SectionContent};
+
+const MAGIC: &[u8; 8] = b"EdEdComp";
+const VERSION: u32 = 1;
+
+pub struct CompositeModule {
+    pub name: String,
+    pub wasm: Vec<u8>,
+}
+
+// Binds every import from `namespace` in module `importer` to the exports of `target`
+pub struct Link {
+    pub importer: String,
+    pub namespace: String,
+    pub target: String,
+}
+
+pub struct Composite {
+    // Stored in instantiation order
+    pub modules: Vec<CompositeModule>,
+    pub links: Vec<Link>,
+    // Module whose exports (and memory) become the Component's
+    pub export: String,
+}
+
+impl Composite {
+    // Manifest format, one directive per line, '#' starts a comment:
+    //     module <name> <path to .wasm, relative to the manifest>
+    //     link <importer> <namespace> -> <target>
+    //     export <name>
+    #[allow(dead_code)] // Only the compose tool reads manifests
+    pub fn from_manifest(path: &str) -> Result<Composite> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format_err!("Failed to read manifest {}: {}", path, e))?;
+        let base = Path::new(path).parent().unwrap_or(Path::new("."));
+        let mut modules = Vec::new();
+        let mut links = Vec::new();
+        let mut export = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {},
+                ["module", name, file] => {
+                    let file = base.join(file);
+                    let wasm = fs::read(&file)
+                        .map_err(|e| format_err!("Failed to read module {}: {}", file.display(), e))?;
+                    modules.push(CompositeModule { name: name.to_string(), wasm });
+                },
+                ["link", importer, namespace, "->", target] => {
+                    links.push(Link {
+                        importer: importer.to_string(),
+                        namespace: namespace.to_string(),
+                        target: target.to_string(),
+                    });
+                },
+                ["export", name] => {
+                    export = Some(name.to_string());
+                },
+                _ => bail!("{}:{}: Unrecognized directive: {}", path, i + 1, line),
+            }
+        }
+        let export = export.ok_or(format_err!("{}: Missing export directive", path))?;
+        let composite = Composite { modules, links, export };
+        composite.validate()?;
+        Ok(composite)
+    }
+
+    // Which internal module (if any) satisfies an importer's namespace
+    pub fn link_target(&self, importer: &str, namespace: &str) -> Option<&str> {
+        self.links.iter()
+            .find(|l| l.importer == importer && l.namespace == namespace)
+            .map(|l| l.target.as_str())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut seen = HashMap::new();
+        for (i, module) in self.modules.iter().enumerate() {
+            if seen.insert(module.name.as_str(), i).is_some() {
+                bail!("Duplicate module name: {}", module.name);
+            }
+        }
+        if !seen.contains_key(self.export.as_str()) {
+            bail!("Export module not found: {}", self.export);
+        }
+        // Each importer's namespace resolves to one module
+        let mut linked = HashMap::new();
+        for link in &self.links {
+            if let Some(target) = linked.insert((link.importer.as_str(), link.namespace.as_str()), link.target.as_str()) {
+                bail!("{}: namespace {} is linked to both {} and {}", link.importer, link.namespace, target, link.target);
+            }
+            let importer_idx = *seen.get(link.importer.as_str())
+                .ok_or(format_err!("Link from unknown module: {}", link.importer))?;
+            let target_idx = *seen.get(link.target.as_str())
+                .ok_or(format_err!("Link to unknown module: {}", link.target))?;
+            if importer_idx == target_idx {
+                bail!("Module {} can't link to itself", link.importer);
+            }
+            let importer = ModuleInterface::parse(&self.modules[importer_idx].wasm)?;
+            let target = ModuleInterface::parse(&self.modules[target_idx].wasm)?;
+            for (namespace, field, kind) in &importer.imports {
+                if namespace != &link.namespace {
+                    continue;
+                }
+                match target.exports.get(field.as_str()) {
+                    Some(export_kind) if export_kind == kind => {},
+                    Some(_) => bail!("{}: import {}/{} doesn't match the kind exported by {}",
+                        link.importer, namespace, field, link.target),
+                    None => bail!("{}: import {}/{} isn't exported by {}",
+                        link.importer, namespace, field, link.target),
+                }
+                // Functions are bound lazily, everything else needs its provider to exist already
+                if *kind != Kind::Func && target_idx > importer_idx {
+                    bail!("{}: non-function import {}/{} requires {} to be listed first",
+                        link.importer, namespace, field, link.target);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_composite(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    #[allow(dead_code)] // Only the compose tool writes composites
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        write_u32(&mut out, VERSION);
+        write_u32(&mut out, self.modules.len() as u32);
+        for module in &self.modules {
+            write_bytes(&mut out, module.name.as_bytes());
+            write_bytes(&mut out, &module.wasm);
+        }
+        write_u32(&mut out, self.links.len() as u32);
+        for link in &self.links {
+            write_bytes(&mut out, link.importer.as_bytes());
+            write_bytes(&mut out, link.namespace.as_bytes());
+            write_bytes(&mut out, link.target.as_bytes());
+        }
+        write_bytes(&mut out, self.export.as_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Composite> {
+        if !Composite::is_composite(bytes) {
+            bail!("Not a composite component");
+        }
+        let mut reader = Reader { bytes, pos: MAGIC.len() };
+        let version = reader.u32()?;
+        if version != VERSION {
+            bail!("Unsupported composite version: {}", version);
+        }
+        let mut modules = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let wasm = reader.bytes()?.to_vec();
+            modules.push(CompositeModule { name, wasm });
+        }
+        let mut links = Vec::new();
+        for _ in 0..reader.u32()? {
+            let importer = reader.string()?;
+            let namespace = reader.string()?;
+            let target = reader.string()?;
+            links.push(Link { importer, namespace, target });
+        }
+        let export = reader.string()?;
+        // Composites come from the compose tool, which validates them, but the
+        // file may not have
+        let composite = Composite { modules, links, export };
+        composite.validate()?;
+        Ok(composite)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+// Just the import/export names of a module, enough to check link wiring
+struct ModuleInterface {
+    imports: Vec<(String, String, Kind)>,
+    exports: HashMap<String, Kind>,
+}
+impl ModuleInterface {
+    fn parse(wasm: &[u8]) -> Result<ModuleInterface> {
+        let mut imports = Vec::new();
+        let mut exports = HashMap::new();
+        let mut reader = ModuleReader::new(wasm)?;
+        while !reader.eof() {
+            match reader.read()?.content()? {
+                SectionContent::Import(section) => {
+                    for import in section {
+                        let import = import?;
+                        let kind = match import.ty {
+                            ImportSectionEntryType::Function(_) => Kind::Func,
+                            ImportSectionEntryType::Table(_) => Kind::Table,
+                            ImportSectionEntryType::Memory(_) => Kind::Memory,
+                            ImportSectionEntryType::Global(_) => Kind::Global,
+                        };
+                        imports.push((import.module.to_string(), import.field.to_string(), kind));
+                    }
+                },
+                SectionContent::Export(section) => {
+                    for export in section {
+                        let export = export?;
+                        let kind = match export.kind {
+                            ExternalKind::Function => Kind::Func,
+                            ExternalKind::Table => Kind::Table,
+                            ExternalKind::Memory => Kind::Memory,
+                            ExternalKind::Global => Kind::Global,
+                        };
+                        exports.insert(export.field.to_string(), kind);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(ModuleInterface { imports, exports })
+    }
+}
+
+// -------------------------
+// Serialization helpers, all integers little-endian
+#[allow(dead_code)]
+fn write_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+#[allow(dead_code)]
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(anyhow!("Unexpected end of composite"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+    fn u32(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    fn string(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.bytes()?.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exports a function f
+    const EXPORTER: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type () -> ()
+        0x03, 0x02, 0x01, 0x00, // one function of type 0
+        0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00, // export "f"
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // empty body
+    ];
+    // Imports a function b/f
+    const IMPORTER: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x02, 0x07, 0x01, 0x01, b'b', 0x01, b'f', 0x00, 0x00, // import "b" "f"
+    ];
+
+    fn composite(export: &str) -> Composite {
+        Composite {
+            modules: vec![
+                CompositeModule { name: "a".to_string(), wasm: IMPORTER.to_vec() },
+                CompositeModule { name: "b".to_string(), wasm: EXPORTER.to_vec() },
+            ],
+            links: vec![Link { importer: "a".to_string(), namespace: "b".to_string(), target: "b".to_string() }],
+            export: export.to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = composite("a");
+        original.validate().unwrap();
+        let bytes = original.to_bytes();
+        assert!(Composite::is_composite(&bytes));
+        let loaded = Composite::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.modules.len(), 2);
+        for (loaded, original) in loaded.modules.iter().zip(&original.modules) {
+            assert_eq!(loaded.name, original.name);
+            assert_eq!(loaded.wasm, original.wasm);
+        }
+        assert_eq!(loaded.links.len(), 1);
+        assert_eq!(loaded.links[0].importer, "a");
+        assert_eq!(loaded.links[0].namespace, "b");
+        assert_eq!(loaded.links[0].target, "b");
+        assert_eq!(loaded.export, "a");
+        assert_eq!(loaded.link_target("a", "b"), Some("b"));
+        assert_eq!(loaded.link_target("b", "b"), None);
+    }
+
+    #[test]
+    fn load_validates() {
+        let bytes = composite("missing").to_bytes();
+        assert!(Composite::from_bytes(&bytes).is_err());
+
+        let mut unsatisfied = composite("a");
+        // b no longer exports the f that a imports from it
+        unsatisfied.modules[1].wasm = IMPORTER.to_vec();
+        assert!(Composite::from_bytes(&unsatisfied.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_links() {
+        let mut duplicated = composite("a");
+        duplicated.modules.push(CompositeModule { name: "c".to_string(), wasm: EXPORTER.to_vec() });
+        duplicated.links.push(Link { importer: "a".to_string(), namespace: "b".to_string(), target: "c".to_string() });
+        assert_eq!(duplicated.validate().unwrap_err().to_string(), "a: namespace b is linked to both b and c");
+        // The same namespace from another importer is fine
+        duplicated.links[1] = Link { importer: "c".to_string(), namespace: "b".to_string(), target: "b".to_string() };
+        duplicated.validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_bytes() {
+        assert!(Composite::from_bytes(b"not a composite").is_err());
+        let bytes = composite("a").to_bytes();
+        assert!(Composite::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut versioned = bytes.clone();
+        versioned[MAGIC.len()] = VERSION as u8 + 1;
+        assert!(Composite::from_bytes(&versioned).is_err());
+    }
+}
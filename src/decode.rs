@@ -0,0 +1,143 @@
+// Decoding image files into the host's pixel format, via SDL_image
+
+use sdl2::{
+    image::{self, ImageRWops, InitFlag},
+    pixels::PixelFormatEnum,
+    rwops::RWops,
+};
+use std::{fmt, fs, io};
+
+// A decoded image, in the usual layout: rows bottom-up, RGBA, straight alpha
+pub struct DecodedImage {
+    pub w: i32,
+    pub h: i32,
+    pub pixels: Vec<u8>,
+}
+
+pub enum LoadError {
+    Unreadable(io::Error),
+    Unsupported,
+    Corrupt(String),
+}
+impl LoadError {
+    // What loadImage hands back to guests instead of an id
+    pub fn code(&self) -> i32 {
+        match self {
+            LoadError::Unreadable(_) => -1,
+            LoadError::Unsupported => -2,
+            LoadError::Corrupt(_) => -3,
+        }
+    }
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Unreadable(e) => write!(f, "couldn't read file: {}", e),
+            LoadError::Unsupported => write!(f, "not a PNG, JPEG, WebP or BMP file"),
+            LoadError::Corrupt(e) => write!(f, "couldn't decode: {}", e),
+        }
+    }
+}
+
+pub fn init() {
+    match image::init(InitFlag::PNG | InitFlag::JPG | InitFlag::WEBP) {
+        // Keeps the decoders loaded for the rest of the program
+        Ok(context) => std::mem::forget(context),
+        Err(e) => println!("Failed to initialize SDL_image: {}", e),
+    }
+}
+
+pub fn load(path: &str) -> Result<DecodedImage, LoadError> {
+    let bytes = fs::read(path).map_err(LoadError::Unreadable)?;
+    // SDL_image would also take TGA, GIF etc. if asked; stick to what we say we support
+    if !is_supported(&bytes) {
+        return Err(LoadError::Unsupported);
+    }
+
+    let surface = RWops::from_bytes(&bytes).and_then(|rw| rw.load())
+        .and_then(|surface| surface.convert_format(PixelFormatEnum::RGBA32))
+        .map_err(LoadError::Corrupt)?;
+    let (w, h, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+    let mut pixels = Vec::with_capacity(w * h * 4);
+    surface.with_lock(|data| {
+        // Surfaces are top-down
+        for y in (0..h).rev() {
+            pixels.extend_from_slice(&data[y * pitch..y * pitch + w * 4]);
+        }
+    });
+    Ok(DecodedImage { w: w as i32, h: h as i32, pixels })
+}
+
+// Checks for a PNG, JPEG, WebP or BMP signature
+fn is_supported(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+        || bytes.starts_with(&[0xff, 0xd8, 0xff])
+        || (bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP")
+        || bytes.starts_with(b"BM")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::File, io::BufWriter, path::PathBuf};
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ed_ed_decode_{}", name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn load_code(path: &PathBuf) -> i32 {
+        match load(path.to_str().unwrap()) {
+            Ok(_) => 0,
+            Err(e) => e.code(),
+        }
+    }
+
+    #[test]
+    fn recognizes_signatures() {
+        assert!(is_supported(b"\x89PNG\r\n\x1a\n..."));
+        assert!(is_supported(&[0xff, 0xd8, 0xff, 0xe0]));
+        assert!(is_supported(b"RIFF\0\0\0\0WEBPVP8 "));
+        assert!(is_supported(b"BM...."));
+        assert!(!is_supported(b"GIF89a"));
+        assert!(!is_supported(b"RIFF\0\0\0\0WAVE"));
+        assert!(!is_supported(b"RIFF"));
+        assert!(!is_supported(b""));
+    }
+
+    #[test]
+    fn reports_why_it_failed() {
+        assert_eq!(load_code(&std::env::temp_dir().join("ed_ed_decode_missing.png")), -1);
+        assert_eq!(load_code(&temp_file("gif.gif", b"GIF89a\x01\0\x01\0")), -2);
+        assert_eq!(load_code(&temp_file("truncated.png", b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR")), -3);
+    }
+
+    #[test]
+    fn loads_rows_bottom_up() {
+        let path = std::env::temp_dir().join("ed_ed_decode_rows.png");
+        {
+            let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path).unwrap()), 2, 2);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            // Top row red then green, bottom row half-transparent blue then clear
+            encoder.write_header().unwrap().write_image_data(&[
+                255, 0, 0, 255,  0, 255, 0, 255,
+                0, 0, 255, 128,  0, 0, 0, 0,
+            ]).unwrap();
+        }
+        let image = load(path.to_str().unwrap()).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!((image.w, image.h), (2, 2));
+        assert_eq!(image.pixels, [
+            0, 0, 255, 128,  0, 0, 0, 0,
+            255, 0, 0, 255,  0, 255, 0, 255,
+        ]);
+    }
+
+    #[test]
+    fn loads_the_canvas_start_image() {
+        let image = load("resources/images/canvas.png").unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!((image.w, image.h), (16, 16));
+        assert_eq!(image.pixels.len(), 16 * 16 * 4);
+    }
+}
@@ -0,0 +1,144 @@
+// Host side of the emscripten runtime, so that standard emcc output instantiates
+// without -s ERROR_ON_UNDEFINED_SYMBOLS=0
+
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    rc::{Rc, Weak},
+};
+
+use wasmtime::*;
+
+use crate::component::{Component, ImportModule};
+
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+// wasi errno values
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_SPIPE: i32 = 70;
+
+pub fn env_module(component: &Rc<RefCell<Component>>) -> ImportModule {
+    let store = component.borrow().store.clone();
+    let mut ret = ImportModule::new();
+    ret.add_func("abort", Func::wrap(&store, || -> Result<(), Trap> {
+        Err(Trap::new("abort() called"))
+    }));
+    ret.add_func("exit", Func::wrap(&store, |code: i32| -> Result<(), Trap> {
+        Err(Trap::new(format!("exit() called w/ code: {}", code)))
+    }));
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("emscripten_memcpy_big", Func::wrap(&store, move |dest: i32, src: i32, num: i32| -> Result<i32, Trap> {
+            let memory = component_memory(&component_weak)?;
+            let (dest, src, num) = (dest as u32 as usize, src as u32 as usize, num as u32 as usize);
+            let data = unsafe { memory.data_unchecked_mut() };
+            if src + num > data.len() || dest + num > data.len() {
+                return Err(Trap::new("emscripten_memcpy_big out of bounds"));
+            }
+            data.copy_within(src..src + num, dest);
+            Ok(dest as i32)
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("emscripten_resize_heap", Func::wrap(&store, move |requested: i32| -> i32 {
+            // Without a memory there's nothing to grow, which is a failed resize
+            let memory = match component_memory(&component_weak) {
+                Ok(memory) => memory,
+                Err(_) => return 0,
+            };
+            let requested = requested as u32 as usize;
+            let current = memory.data_size();
+            if requested <= current {
+                return 1;
+            }
+            let pages = (requested - current + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+            match memory.grow(pages as u32) {
+                Ok(_) => 1,
+                Err(_) => 0,
+            }
+        }));
+    }
+    // Upper 32 bits of i64 returns, for modules built without BigInt support
+    let temp_ret = Rc::new(Cell::new(0));
+    {
+        let temp_ret = temp_ret.clone();
+        ret.add_func("setTempRet0", Func::wrap(&store, move |v: i32| temp_ret.set(v)));
+    }
+    ret.add_func("getTempRet0", Func::wrap(&store, move || temp_ret.get()));
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("print", Func::wrap(&store, move |ptr: i32, len: i32| -> Result<(), Trap> {
+            let memory = component_memory(&component_weak)?;
+            let data = unsafe { memory.data_unchecked() };
+            let bytes = guest_bytes(data, ptr as u32, len as u32, "print")?;
+            print!("{}", String::from_utf8_lossy(bytes));
+            Ok(())
+        }));
+    }
+    ret
+}
+
+// The subset of wasi that emscripten's libc reaches for when printing
+pub fn wasi_module(component: &Rc<RefCell<Component>>) -> ImportModule {
+    let store = component.borrow().store.clone();
+    let mut ret = ImportModule::new();
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("fd_write", Func::wrap(&store, move |fd: i32, iovs: i32, iovs_len: i32, nwritten: i32| -> Result<i32, Trap> {
+            let memory = component_memory(&component_weak)?;
+            let data = unsafe { memory.data_unchecked_mut() };
+            let iov_bytes = (iovs_len as u32).checked_mul(8)
+                .ok_or_else(|| Trap::new("fd_write: iovs out of bounds"))?;
+            let iovs = guest_bytes(data, iovs as u32, iov_bytes, "fd_write iovs")?;
+            let mut bytes = Vec::new();
+            for iov in iovs.chunks_exact(8) {
+                let ptr = read_u32(iov, 0);
+                let len = read_u32(iov, 4);
+                bytes.extend_from_slice(guest_bytes(data, ptr, len, "fd_write")?);
+            }
+            let result = match fd {
+                1 => std::io::stdout().write_all(&bytes),
+                2 => std::io::stderr().write_all(&bytes),
+                _ => return Ok(ERRNO_BADF),
+            };
+            if result.is_err() {
+                return Ok(ERRNO_BADF);
+            }
+            let written = (bytes.len() as u32).to_le_bytes();
+            let start = nwritten as u32 as usize;
+            data.get_mut(start..start + 4)
+                .ok_or_else(|| Trap::new("fd_write: nwritten out of bounds"))?
+                .copy_from_slice(&written);
+            Ok(ERRNO_SUCCESS)
+        }));
+    }
+    ret.add_func("fd_close", Func::wrap(&store, |_fd: i32| -> i32 { ERRNO_SUCCESS }));
+    ret.add_func("fd_seek", Func::wrap(&store, |_fd: i32, _offset: i64, _whence: i32, _newoffset: i32| -> i32 {
+        ERRNO_SPIPE
+    }));
+    ret.add_func("proc_exit", Func::wrap(&store, |code: i32| -> Result<(), Trap> {
+        Err(Trap::new(format!("wasi proc_exit called w/ code: {}", code)))
+    }));
+    ret
+}
+
+fn component_memory(component: &Weak<RefCell<Component>>) -> Result<Memory, Trap> {
+    component.upgrade().unwrap().borrow().memory()
+}
+
+// `len` bytes of guest memory from `ptr`, or a trap naming `what` if that runs
+// off the end
+fn guest_bytes<'a>(data: &'a [u8], ptr: u32, len: u32, what: &str) -> Result<&'a [u8], Trap> {
+    let (ptr, len) = (ptr as usize, len as usize);
+    ptr.checked_add(len)
+        .and_then(|end| data.get(ptr..end))
+        .ok_or_else(|| Trap::new(format!("{}: {} bytes at {} out of bounds", what, len, ptr)))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
@@ -3,31 +3,121 @@
 extern crate sdl2;
 extern crate gl;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use sdl2::{
-    event::Event,
-    keyboard::Keycode,
-    mouse::MouseButton,
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod, Scancode},
+    mouse::{MouseButton, MouseWheelDirection},
+    video::SwapInterval,
 };
 use std::{
-    time::Duration,
+    cell::RefCell,
+    rc::Rc,
 };
 
 use wasmtime::*;
 
+mod capture;
 mod component;
+mod decode;
+mod composite;
+mod emscripten;
+mod math;
 mod renderer;
+mod shapes;
+mod shortcuts;
+mod text;
+mod timing;
 use component::{Component, Imports, WrappedComponent};
-use renderer::Renderer;
+use renderer::{GlRenderer, SoftwareRenderer, Viewport};
+use timing::{Clock, Pacing};
+
+// Command line flags
+struct Options {
+    // Render with the CPU rasterizer instead of opening a window
+    headless: bool,
+    // Quit after this many frames
+    frames: Option<u64>,
+    // Save every N-th frame, for recordings
+    record_every: Option<u64>,
+    // Save every frame's render commands as text, e.g. for diffing against a known-good run
+    dump_commands: bool,
+    // Cap the frame rate by sleeping, even if the display could do vsync
+    no_vsync: bool,
+    // Draw every frame, even when nothing's animating
+    no_idle: bool,
+}
+impl Options {
+    fn parse() -> Result<Options> {
+        let mut options = Options {
+            headless: false,
+            frames: None,
+            record_every: None,
+            dump_commands: false,
+            no_vsync: false,
+            no_idle: false,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--dump-commands" => options.dump_commands = true,
+                "--no-vsync" => options.no_vsync = true,
+                "--no-idle" => options.no_idle = true,
+                "--frames" => {
+                    let n = args.next().ok_or(anyhow!("--frames needs a count"))?;
+                    options.frames = Some(n.parse()?);
+                },
+                "--record" => {
+                    let n: u64 = args.next().ok_or(anyhow!("--record needs a frame interval"))?.parse()?;
+                    if n == 0 {
+                        bail!("--record interval must be at least 1");
+                    }
+                    options.record_every = Some(n);
+                },
+                _ => bail!("Unrecognized argument: {}", arg),
+            }
+        }
+        Ok(options)
+    }
+}
 
 fn main() -> Result<()> {
-    pixel_editor()
-    // notes_app()
+    let options = Options::parse()?;
+    pixel_editor(&options)
+    // notes_app(&options)
 }
 
-// fn _notes_app() -> Result<()> {
-//     let render = Renderer::new();
+// Sets up the render backend the options ask for, and how its frames are paced.
+// The SDL context is still needed headless, for events.
+fn init_renderer(options: &Options) -> (sdl2::Sdl, Pacing) {
+    println!("Initializing SDL...");
+    let sdl_context = sdl2::init().unwrap();
+    if options.headless {
+        println!("Rendering headless");
+        renderer::init(Box::new(SoftwareRenderer::new(800, 600)));
+        return (sdl_context, Pacing::Simulated);
+    }
+    renderer::init(Box::new(GlRenderer::new(&sdl_context)));
+    // On by default on desktops, but IMEs only compose while it's on
+    sdl_context.video().unwrap().text_input().start();
+    let interval = if options.no_vsync { SwapInterval::Immediate } else { SwapInterval::VSync };
+    let pacing = match sdl_context.video().unwrap().gl_set_swap_interval(interval) {
+        Ok(()) if !options.no_vsync => Pacing::VSync,
+        Ok(()) => Pacing::Capped,
+        Err(e) => {
+            println!("No vsync, capping the frame rate instead: {}", e);
+            Pacing::Capped
+        },
+    };
+    (sdl_context, pacing)
+}
+
+// fn _notes_app(options: &Options) -> Result<()> {
+//     let (sdl_context, pacing) = init_renderer(options);
 //     let store = Store::default();
+//     shortcuts::load_bindings(shortcuts::BINDINGS_PATH)?;
+//     let quit = shortcuts::register("app.quit");
 
 //     let input_rc = Component::init(&store);
 //     input_rc.borrow_mut().instance = Some(Component::initialize(&input_rc, "modules/out/input.wasm", Imports::new())?);
@@ -35,155 +125,524 @@ fn main() -> Result<()> {
 
 //     let notes_rc = Component::init(&store);
 //     let notes_imports = Imports::from_vec(vec![
-//         ("render", Renderer::import_module(&notes_rc)),
+//         ("render", renderer::import_module(&notes_rc)),
 //         ("input", input_ref.get_exports()),
+//         ("time", timing::import_module(&store)),
+//         ("commands", shortcuts::import_module(&notes_rc)),
 //     ]);
-//     notes_rc.borrow_mut().instance = Some(Component::initialize(&notes_rc, "modules/out/notes.wasm", notes_imports)?);
+//     notes_rc.borrow_mut().instance = Some(Component::initialize(&notes_rc, "modules/out/notes.comp", notes_imports)?);
 //     let notes_ref = notes_rc.borrow();
 
 //     println!("Extracting exports...");
 //     let notes_update = notes_ref.get_func("update")?.get0::<()>()?;
 
 //     let input_update = input_ref.get_func("update")?.get0::<()>()?;
-//     let mouse_event = input_ref.get_func("onMouseEvent")?.get3::<i32, i32, i32, ()>()?;
-//     let key_event = input_ref.get_func("onKeyEvent")?.get2::<i32, i32, ()>()?;
+//     let mouse_event = input_ref.get_func("onMouseEvent")?.get5::<i32, i32, i32, i32, i32, ()>()?;
+//     let key_event = input_ref.get_func("onKeyEvent")?.get4::<i32, i32, i32, i32, ()>()?;
+//     let text_input = input_ref.get_func("onTextInput")?.get1::<i32, ()>()?;
 
 //     println!("Starting main loop");
-//     let mut event_pump = render.sdl_context.event_pump().unwrap();
-//     let canvas_x = 200;
-//     let canvas_y = 150;
+//     let mut event_pump = sdl_context.event_pump().unwrap();
+//     let mut clock = Clock::new(pacing);
 //     let to_canvas_space = |x: i32, y: i32| -> (i32, i32) {
-//         (x - canvas_x, 600 - y - canvas_y)
+//         let (w, h) = renderer::screen_size();
+//         let (w, h) = (w as i32, h as i32);
+//         (x - w / 4, h - y - h / 4)
 //     };
+//     let mut frame = 0;
 //     'mainloop: loop {
-//         unsafe {
-//             gl::Viewport(0, 0, 800, 600);
-//             gl::ClearColor(0.8, 0.8, 0.8, 1.0);
-//             gl::Clear(gl::COLOR_BUFFER_BIT);
-//         }
 //         input_update()?; // TODO: figure out generic timing on this
-//         for event in event_pump.poll_iter() {
+//         shortcuts::begin_frame();
+//         let mut events = Vec::new();
+//         if clock.can_idle() && !options.no_idle {
+//             events.push(event_pump.wait_event());
+//         }
+//         events.extend(event_pump.poll_iter());
+//         for event in events {
 //             match event {
-//                 Event::Quit {..} |
-//                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+//                 Event::Quit {..} => {
 //                     break 'mainloop
 //                 },
-//                 Event::KeyDown { keycode: Some(code), .. } => {
-//                     key_event(0, code as i32)?;
+//                 Event::KeyDown { scancode, keycode, keymod, repeat, .. } => {
+//                     if shortcuts::key_down(scancode, keycode, keymod, repeat, Some(&notes_rc)) {
+//                         continue;
+//                     }
+//                     let (scancode, keycode, mods) = key_codes(scancode, keycode, keymod);
+//                     key_event(if repeat { 2 } else { 0 }, scancode, keycode, mods)?;
 //                 },
-//                 Event::KeyUp { keycode: Some(code), .. } => {
-//                     key_event(1, code as i32)?;
+//                 Event::KeyUp { scancode, keycode, keymod, .. } => {
+//                     if shortcuts::key_up(scancode) {
+//                         continue;
+//                     }
+//                     let (scancode, keycode, mods) = key_codes(scancode, keycode, keymod);
+//                     key_event(1, scancode, keycode, mods)?;
+//                 },
+//                 Event::TextInput { text, .. } => {
+//                     if shortcuts::swallows_text() {
+//                         continue;
+//                     }
+//                     for byte in text.bytes() {
+//                         text_input(byte as i32)?;
+//                     }
 //                 },
 //                 Event::MouseMotion { x, y, .. } => {
 //                     let (x, y) = to_canvas_space(x, y);
-//                     mouse_event(0, x, y)?;
+//                     mouse_event(0, x, y, 0, 0)?;
 //                 },
-//                 Event::MouseButtonDown { mouse_btn, x, y, .. } => {
-//                     if mouse_btn == MouseButton::Left {
-//                         let (x, y) = to_canvas_space(x, y);
-//                         mouse_event(1, x, y)?;
-//                     }
+//                 Event::MouseButtonDown { mouse_btn, clicks, x, y, .. } => {
+//                     let (x, y) = to_canvas_space(x, y);
+//                     mouse_event(1, x, y, button_number(mouse_btn), clicks as i32)?;
 //                 },
-//                 Event::MouseButtonUp { mouse_btn, x, y, .. } => {
-//                     if mouse_btn == MouseButton::Left {
-//                         let (x, y) = to_canvas_space(x, y);
-//                         mouse_event(2, x, y)?;
-//                     }
+//                 Event::MouseButtonUp { mouse_btn, clicks, x, y, .. } => {
+//                     let (x, y) = to_canvas_space(x, y);
+//                     mouse_event(2, x, y, button_number(mouse_btn), clicks as i32)?;
 //                 },
 //                 _ => {}
 //             }
 //         }
+//         if shortcuts::triggered(quit) > 0 {
+//             break 'mainloop;
+//         }
 
-//         render.pre_update();
+//         if let Some(every) = options.record_every {
+//             if frame % every == 0 {
+//                 renderer::capture_frame(capture::frame_path(frame / every));
+//             }
+//         }
+//         if options.dump_commands {
+//             renderer::dump_commands(capture::commands_path(frame));
+//         }
+//         clock.begin_frame();
+//         renderer::pre_update();
 //         notes_update()?;
-//         render.post_update();
-
-//         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+//         renderer::post_update();
+//         frame += 1;
+//         if options.frames == Some(frame) {
+//             break 'mainloop;
+//         }
+//         clock.end_frame();
 //     }
 
 //     println!("Done.");
 //     Ok(())
 // }
 
-fn pixel_editor() -> Result<()> {
-    let render = Renderer::new();
-    let store = Store::default();
+// A component with its own viewport of the window. It draws in, and gets mouse
+// positions in, pixel space local to the viewport.
+struct Panel {
+    component: Rc<RefCell<Component>>,
+    // Each panel polls its own input, which only has what was routed to it, with
+    // positions in its local space
+    input: Rc<RefCell<Component>>,
+    // Higher layers draw on top
+    layer: i32,
+    // Where the panel goes, for a given window size
+    layout: fn(i32, i32) -> Viewport,
+    viewport: Viewport,
+}
+impl Panel {
+    fn load(store: &Store, filename: &str, layer: i32, layout: fn(i32, i32) -> Viewport) -> Result<Panel> {
+        let input = Component::init(store);
+        input.borrow_mut().instance = Some(Component::initialize(&input, "modules/out/input.wasm", Imports::new())?);
 
-    let input_rc = Component::init(&store);
-    input_rc.borrow_mut().instance = Some(Component::initialize(&input_rc, "modules/out/input.wasm", Imports::new())?);
-    let input_ref = input_rc.borrow();
+        let component = Component::init(store);
+        let imports = Panel::imports(&component, &input);
+        component.borrow_mut().instance = Some(Component::initialize(&component, filename, imports)?);
 
+        let (w, h) = renderer::screen_size();
+        let viewport = layout(w as i32, h as i32);
+        Ok(Panel { component, input, layer, layout, viewport })
+    }
 
-    let texture_ref = WrappedComponent::loader(&store, |rc| {
+    fn imports(component: &Rc<RefCell<Component>>, input: &Rc<RefCell<Component>>) -> Imports {
+        let store = component.borrow().store.clone();
+        let texture_ref = WrappedComponent::loader(&store, |rc| {
+            Imports::from_vec(vec![
+                ("render", renderer::import_module(rc)),
+            ])
+        });
         Imports::from_vec(vec![
-            ("render", Renderer::import_module(rc)),
+            ("render", renderer::import_module(component)),
+            ("input", input.borrow().get_exports()),
+            ("texture", texture_ref),
+            ("time", timing::import_module(&store)),
+            ("commands", shortcuts::import_module(component)),
         ])
-    });
+    }
+
+    // Loads the component's file again, e.g. after rebuilding it. Everything the
+    // old instance owned is freed, and the new one starts over from init.
+    fn reload(&self) -> Result<()> {
+        Component::reload(&self.component, Panel::imports(&self.component, &self.input))?;
+        self.call("init")
+    }
+
+    // Calls an export with no arguments, if the component has it
+    fn call(&self, name: &str) -> Result<()> {
+        // Don't hold borrows across calls into wasm; imports may need to mutate their Component
+        let func = self.component.borrow().get_func(name).ok();
+        if let Some(func) = func {
+            let func = func.get0::<()>()?;
+            renderer::with_viewport(self.viewport, self.layer, func)?;
+        }
+        Ok(())
+    }
+
+    // Lays the panel out again for a new window size. Components exporting
+    // onResize(w, h) get told the size of their new viewport.
+    fn resize(&mut self, w: i32, h: i32) -> Result<()> {
+        self.viewport = (self.layout)(w, h);
+        let on_resize = self.component.borrow().get_func("onResize").ok();
+        if let Some(on_resize) = on_resize {
+            let on_resize = on_resize.get2::<i32, i32, ()>()?;
+            let (w, h) = (self.viewport.w, self.viewport.h);
+            renderer::with_viewport(self.viewport, self.layer, || on_resize(w, h))?;
+        }
+        Ok(())
+    }
+
+    fn update_input(&self) -> Result<()> {
+        let update = self.input.borrow().get_func("update")?.get0::<()>()?;
+        update()?;
+        Ok(())
+    }
+
+    // Events are 0 for down, 1 for up, 2 for an auto-repeat
+    fn key_event(&self, event: i32, scancode: i32, keycode: i32, mods: i32) -> Result<()> {
+        let key_event = self.input.borrow().get_func("onKeyEvent")?.get4::<i32, i32, i32, i32, ()>()?;
+        key_event(event, scancode, keycode, mods)?;
+        Ok(())
+    }
+
+    // Typed text, UTF-8. The input component takes it a byte at a time.
+    fn text_input(&self, text: &str) -> Result<()> {
+        let text_input = self.input.borrow().get_func("onTextInput")?.get1::<i32, ()>()?;
+        for byte in text.bytes() {
+            text_input(byte as i32)?;
+        }
+        Ok(())
+    }
+
+    // Text an IME is still composing, replacing whatever it had before. SDL gives the
+    // cursor and selection in characters; the input component has them in bytes.
+    fn text_editing(&self, text: &str, start: i32, length: i32) -> Result<()> {
+        let byte_offset = |chars: i32| text.char_indices().nth(chars.max(0) as usize).map_or(text.len(), |(i, _)| i);
+        let cursor = byte_offset(start);
+        let selection = byte_offset(start + length.max(0)) - cursor;
+        let text_editing = self.input.borrow().get_func("onTextEditing")?.get2::<i32, i32, ()>()?;
+        let text_editing_byte = self.input.borrow().get_func("onTextEditingByte")?.get1::<i32, ()>()?;
+        text_editing(cursor as i32, selection as i32)?;
+        for byte in text.bytes() {
+            text_editing_byte(byte as i32)?;
+        }
+        Ok(())
+    }
+
+    // Window coordinates have their origin at the top-left, pixel space at the bottom-left
+    fn to_local(&self, x: i32, y: i32) -> (i32, i32) {
+        let (_, screen_h) = renderer::screen_size();
+        (x - self.viewport.x, screen_h as i32 - y - self.viewport.y)
+    }
+
+    // In window coordinates
+    fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = self.to_local(x, y);
+        x >= 0 && y >= 0 && x < self.viewport.w && y < self.viewport.h
+    }
+
+    // Events are 0 for a move, 1 for a button down, 2 for a button up, 3 for the
+    // pointer leaving; only downs and ups have a button.
+    fn mouse_event(&self, event: i32, x: i32, y: i32, button: i32, clicks: i32) -> Result<()> {
+        let (x, y) = self.to_local(x, y);
+        let mouse_event = self.input.borrow().get_func("onMouseEvent")?.get5::<i32, i32, i32, i32, i32, ()>()?;
+        mouse_event(event, x, y, button, clicks)?;
+        Ok(())
+    }
+
+    // Relative motion, in window coordinates, so y is flipped to point up
+    fn mouse_motion(&self, dx: i32, dy: i32) -> Result<()> {
+        let mouse_motion = self.input.borrow().get_func("onMouseMotion")?.get2::<i32, i32, ()>()?;
+        mouse_motion(dx, -dy)?;
+        Ok(())
+    }
+
+    fn mouse_wheel(&self, x: f32, y: f32) -> Result<()> {
+        let mouse_wheel = self.input.borrow().get_func("onMouseWheel")?.get2::<f32, f32, ()>()?;
+        mouse_wheel(x, y)?;
+        Ok(())
+    }
+
+    // Components exporting onFocusChanged(focused) get told when they gain or lose
+    // the keyboard focus
+    fn focus_changed(&self, focused: bool) -> Result<()> {
+        let on_focus = self.input.borrow().get_func("onFocus")?.get1::<i32, ()>()?;
+        on_focus(focused as i32)?;
+        let on_focus_changed = self.component.borrow().get_func("onFocusChanged").ok();
+        if let Some(on_focus_changed) = on_focus_changed {
+            let on_focus_changed = on_focus_changed.get1::<i32, ()>()?;
+            renderer::with_viewport(self.viewport, self.layer, || on_focus_changed(focused as i32))?;
+        }
+        Ok(())
+    }
+}
 
-    let canvas_rc = Component::init(&store);
-    let canvas_imports = Imports::from_vec(vec![
-        ("render", Renderer::import_module(&canvas_rc)),
-        ("input", input_ref.get_exports()),
-        ("texture", texture_ref),
-    ]);
-    canvas_rc.borrow_mut().instance = Some(Component::initialize(&canvas_rc, "modules/out/canvas.wasm", canvas_imports)?);
-    let canvas_ref = canvas_rc.borrow();
+// Decides which panel gets each input event. Pointer events go to the topmost
+// panel under the pointer, except during a drag, when the panel the first button
+// went down in has them until the last one's up. Keys and text go to the panel
+// with the keyboard focus, which is whichever was last clicked.
+struct Router {
+    // Index of the panel pointer events last went to
+    pointer_target: Option<usize>,
+    // The panel a drag started in
+    capture: Option<usize>,
+    // Bits by button number
+    buttons: u32,
+    focus: Option<usize>,
+}
+impl Router {
+    fn new() -> Router {
+        Router { pointer_target: None, capture: None, buttons: 0, focus: None }
+    }
 
-    println!("Extracting exports...");
-    let init = canvas_ref.get_func("init")?.get0::<()>()?;
-    let canvas_update = canvas_ref.get_func("update")?.get0::<()>()?;
+    // Panels later in the list are on top of ones on the same layer, the same as
+    // they draw
+    fn hit(panels: &[Panel], x: i32, y: i32) -> Option<usize> {
+        panels.iter().enumerate()
+            .filter(|(_, panel)| panel.contains(x, y))
+            .max_by_key(|&(i, panel)| (panel.layer, i))
+            .map(|(i, _)| i)
+    }
 
-    let input_update = input_ref.get_func("update")?.get0::<()>()?;
-    let mouse_event = input_ref.get_func("onMouseEvent")?.get3::<i32, i32, i32, ()>()?;
+    // Where a pointer event at (x, y) goes. The last panel to get pointer events
+    // is told the pointer left, if it's moved on.
+    fn pointer<'a>(&mut self, panels: &'a [Panel], x: i32, y: i32) -> Result<Option<&'a Panel>> {
+        let target = self.capture.or_else(|| Router::hit(panels, x, y));
+        if let Some(last) = self.pointer_target.filter(|&last| Some(last) != target) {
+            panels[last].mouse_event(3, x, y, 0, 0)?;
+        }
+        self.pointer_target = target;
+        Ok(target.map(|i| &panels[i]))
+    }
+
+    fn button_down<'a>(&mut self, panels: &'a [Panel], x: i32, y: i32, button: i32) -> Result<Option<&'a Panel>> {
+        let target = self.pointer(panels, x, y)?;
+        if self.buttons == 0 {
+            self.capture = self.pointer_target;
+        }
+        self.buttons |= 1 << button;
+        if target.is_some() {
+            self.set_focus(panels, self.pointer_target)?;
+        }
+        Ok(target)
+    }
+
+    fn button_up<'a>(&mut self, panels: &'a [Panel], x: i32, y: i32, button: i32) -> Result<Option<&'a Panel>> {
+        let target = self.pointer(panels, x, y)?;
+        self.buttons &= !(1 << button);
+        if self.buttons == 0 {
+            self.capture = None;
+        }
+        Ok(target)
+    }
+
+    // The wheel has no position of its own, it goes where the pointer last was
+    fn wheel<'a>(&self, panels: &'a [Panel]) -> Option<&'a Panel> {
+        self.pointer_target.map(|i| &panels[i])
+    }
+
+    fn focused<'a>(&self, panels: &'a [Panel]) -> Option<&'a Panel> {
+        self.focus.map(|i| &panels[i])
+    }
+
+    fn set_focus(&mut self, panels: &[Panel], focus: Option<usize>) -> Result<()> {
+        if focus == self.focus {
+            return Ok(());
+        }
+        if let Some(last) = self.focus {
+            panels[last].focus_changed(false)?;
+        }
+        shortcuts::focus_changed();
+        self.focus = focus;
+        if let Some(focus) = focus {
+            panels[focus].focus_changed(true)?;
+        }
+        Ok(())
+    }
+}
+
+// Buttons cross to components as SDL numbers them: 1 left, 2 middle, 3 right, 4 and 5
+// the extra ones. 0 for anything else.
+fn button_number(button: MouseButton) -> i32 {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::X1 => 4,
+        MouseButton::X2 => 5,
+        MouseButton::Unknown => 0,
+    }
+}
+
+// In wheel ticks, positive y up and positive x right, whatever the system's scroll
+// direction setting. SDL 2.0.18 adds precise (fractional) deltas for trackpads, but
+// the sdl2 crate we're on only has whole ticks, so for now they come through as those.
+fn wheel_delta(x: i32, y: i32, direction: MouseWheelDirection) -> (f32, f32) {
+    match direction {
+        MouseWheelDirection::Flipped => (-x as f32, -y as f32),
+        _ => (x as f32, y as f32),
+    }
+}
+
+// Keys cross to components as SDL scancode, keycode and KMOD_ bits, with -1 for
+// a scancode or keycode SDL doesn't know
+fn key_codes(scancode: Option<Scancode>, keycode: Option<Keycode>, keymod: Mod) -> (i32, i32, i32) {
+    (scancode.map_or(-1, |code| code as i32), keycode.map_or(-1, |code| code as i32), keymod.bits() as i32)
+}
+
+fn pixel_editor(options: &Options) -> Result<()> {
+    let (sdl_context, pacing) = init_renderer(options);
+    let store = Store::default();
+    shortcuts::load_bindings(shortcuts::BINDINGS_PATH)?;
+    let quit = shortcuts::register("app.quit");
+    let screenshot = shortcuts::register("app.screenshot");
+    let reload = shortcuts::register("app.reload");
+
+    // The app's panels, each with its z-layer and layout
+    let mut panels = vec![
+        Panel::load(&store, "modules/out/canvas.wasm", 0, |w, h| Viewport { x: 0, y: 0, w, h })?,
+    ];
 
     println!("Starting main loop");
-    init()?;
-    let mut event_pump = render.sdl_context.event_pump().unwrap();
-    let canvas_x = 200;
-    let canvas_y = 150;
-    let to_canvas_space = |x: i32, y: i32| -> (i32, i32) {
-        (x - canvas_x, 600 - y - canvas_y)
-    };
+    for panel in &panels {
+        panel.call("init")?;
+    }
+    shortcuts::report_conflicts();
+    // The first panel starts out with the keyboard
+    let mut router = Router::new();
+    router.set_focus(&panels, if panels.is_empty() { None } else { Some(0) })?;
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut clock = Clock::new(pacing);
+    let mut frame = 0;
     'mainloop: loop {
-        unsafe {
-            gl::Viewport(0, 0, 800, 600);
-            gl::ClearColor(0.8, 0.8, 0.8, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+        for panel in &panels {
+            panel.update_input()?; // TODO: figure out generic timing on this
+        }
+        shortcuts::begin_frame();
+        // Nothing will change until there's input, so wait for some
+        let mut events = Vec::new();
+        if clock.can_idle() && !options.no_idle {
+            events.push(event_pump.wait_event());
+            clock.idled();
         }
-        input_update()?; // TODO: figure out generic timing on this
-        for event in event_pump.poll_iter() {
+        events.extend(event_pump.poll_iter());
+        for event in events {
             match event {
-                Event::Quit {..} |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                Event::Quit {..} => {
                     break 'mainloop
                 },
-                Event::MouseMotion { x, y, .. } => {
-                    let (x, y) = to_canvas_space(x, y);
-                    mouse_event(0, x, y)?;
+                Event::Window { win_event: WindowEvent::SizeChanged(w, h), .. } => {
+                    for panel in &mut panels {
+                        panel.resize(w, h)?;
+                    }
+                },
+                Event::KeyDown { scancode, keycode, keymod, repeat, .. } => {
+                    let focus = router.focused(&panels).map(|panel| &panel.component);
+                    if shortcuts::key_down(scancode, keycode, keymod, repeat, focus) {
+                        continue;
+                    }
+                    let (scancode, keycode, mods) = key_codes(scancode, keycode, keymod);
+                    if let Some(panel) = router.focused(&panels) {
+                        panel.key_event(if repeat { 2 } else { 0 }, scancode, keycode, mods)?;
+                    }
+                },
+                Event::KeyUp { scancode, keycode, keymod, .. } => {
+                    if shortcuts::key_up(scancode) {
+                        continue;
+                    }
+                    let (scancode, keycode, mods) = key_codes(scancode, keycode, keymod);
+                    if let Some(panel) = router.focused(&panels) {
+                        panel.key_event(1, scancode, keycode, mods)?;
+                    }
+                },
+                Event::TextInput { text, .. } => {
+                    if shortcuts::swallows_text() {
+                        continue;
+                    }
+                    if let Some(panel) = router.focused(&panels) {
+                        panel.text_input(&text)?;
+                    }
+                },
+                Event::TextEditing { text, start, length, .. } => {
+                    if let Some(panel) = router.focused(&panels) {
+                        panel.text_editing(&text, start, length)?;
+                    }
+                },
+                Event::MouseMotion { x, y, xrel, yrel, .. } => {
+                    if let Some(panel) = router.pointer(&panels, x, y)? {
+                        panel.mouse_event(0, x, y, 0, 0)?;
+                        panel.mouse_motion(xrel, yrel)?;
+                    }
+                },
+                Event::MouseButtonDown { mouse_btn, clicks, x, y, .. } => {
+                    let button = button_number(mouse_btn);
+                    if let Some(panel) = router.button_down(&panels, x, y, button)? {
+                        panel.mouse_event(1, x, y, button, clicks as i32)?;
+                    }
                 },
-                Event::MouseButtonDown { mouse_btn, x, y, .. } => {
-                    if mouse_btn == MouseButton::Left {
-                        let (x, y) = to_canvas_space(x, y);
-                        mouse_event(1, x, y)?;
+                Event::MouseButtonUp { mouse_btn, clicks, x, y, .. } => {
+                    let button = button_number(mouse_btn);
+                    if let Some(panel) = router.button_up(&panels, x, y, button)? {
+                        panel.mouse_event(2, x, y, button, clicks as i32)?;
                     }
                 },
-                Event::MouseButtonUp { mouse_btn, x, y, .. } => {
-                    if mouse_btn == MouseButton::Left {
-                        let (x, y) = to_canvas_space(x, y);
-                        mouse_event(2, x, y)?;
+                Event::MouseWheel { x, y, direction, .. } => {
+                    let (x, y) = wheel_delta(x, y, direction);
+                    if let Some(panel) = router.wheel(&panels) {
+                        panel.mouse_wheel(x, y)?;
                     }
                 },
                 _ => {}
             }
         }
+        if shortcuts::triggered(quit) > 0 {
+            break 'mainloop;
+        }
+        if shortcuts::triggered(screenshot) > 0 {
+            renderer::capture_screenshot();
+        }
+        if shortcuts::triggered(reload) > 0 {
+            for panel in &panels {
+                panel.reload()?;
+            }
+        }
 
-        render.pre_update();
-        canvas_update()?;
-        render.post_update();
-
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        if let Some(every) = options.record_every {
+            if frame % every == 0 {
+                renderer::capture_frame(capture::frame_path(frame / every));
+            }
+        }
+        if options.dump_commands {
+            renderer::dump_commands(capture::commands_path(frame));
+        }
+        let steps = clock.begin_frame();
+        renderer::pre_update();
+        for _ in 0..steps {
+            for panel in &panels {
+                timing::fixed_step(|| panel.call("fixedUpdate"))?;
+            }
+        }
+        for panel in &panels {
+            panel.call("update")?;
+        }
+        renderer::post_update();
+        frame += 1;
+        if options.frames == Some(frame) {
+            break 'mainloop;
+        }
+        clock.end_frame();
     }
 
     println!("Done.");
+    WrappedComponent::report_leaks();
     Ok(())
 }
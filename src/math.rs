@@ -0,0 +1,33 @@
+// Matrix helpers for the renderer. Matrices are column-major, as GL expects.
+
+pub type Mat4 = [f32; 16];
+
+// Maps pixel coordinates, origin at the bottom-left, to clip space
+pub fn ortho(width: f32, height: f32) -> Mat4 {
+    [
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, 2.0 / height, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -1.0, -1.0, 0.0, 1.0,
+    ]
+}
+
+// Maps the unit quad (centered at 0) onto a pixel rect, scaled and rotated
+// (counter-clockwise, in radians) around the rect's center
+pub fn rect_transform(x: f32, y: f32, w: f32, h: f32, scale: f32, rotation: f32) -> Mat4 {
+    let (sin, cos) = rotation.sin_cos();
+    let (sw, sh) = (w * scale, h * scale);
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    [
+        cos * sw, sin * sw, 0.0, 0.0,
+        -sin * sh, cos * sh, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        cx, cy, 0.0, 1.0,
+    ]
+}
+
+// Unpacks a guest color (r in the low byte) into normalized RGBA
+pub fn unpack_color(color: i32) -> [f32; 4] {
+    let [r, g, b, a] = color.to_le_bytes();
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0]
+}
@@ -0,0 +1,322 @@
+// Shared atlas pages for small images
+//
+// Entries are packed onto pages in shelves, rows as tall as the tallest entry
+// placed in them. Every entry on a page draws from the same texture, so they
+// batch together. Entries get ids from their own range, above any image id, so
+// draw imports can take either. Pages whose entries are all removed are dropped.
+
+use std::collections::HashMap;
+
+// Pages are square, this many pixels a side
+pub const PAGE_SIZE: i32 = 1024;
+// Left clear to the right of and above each entry, so neighbors don't bleed in
+const PADDING: i32 = 1;
+const FIRST_ENTRY_ID: u32 = 0x4000_0000;
+
+// Where an entry ended up
+#[derive(Clone, Copy)]
+pub struct Placement {
+    // Image id of the page
+    pub page: u32,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+impl Placement {
+    // Maps a UV rect within the entry to one within its page
+    pub fn source_rect(&self, source: [f32; 4]) -> [f32; 4] {
+        let size = PAGE_SIZE as f32;
+        let [u, v, uw, vh] = source;
+        [
+            (self.x as f32 + u * self.w as f32) / size,
+            (self.y as f32 + v * self.h as f32) / size,
+            uw * self.w as f32 / size,
+            vh * self.h as f32 / size,
+        ]
+    }
+}
+
+struct Entry {
+    placement: Placement,
+    // The space it took up, padding included, given back on removal
+    slot: [i32; 4],
+}
+
+struct Shelf {
+    y: i32,
+    h: i32,
+    // Where the next entry on the shelf goes
+    x: i32,
+}
+
+struct Page {
+    id: u32,
+    // Kept so entries can be uploaded as regions of the page
+    pixels: Vec<u8>,
+    shelves: Vec<Shelf>,
+    // Slots of removed entries, reused by anything that fits
+    free: Vec<[i32; 4]>,
+    // Entries still placed on the page
+    entries: usize,
+}
+impl Page {
+    // Room for a padded w*h slot, if there's any left
+    fn find_slot(&mut self, w: i32, h: i32) -> Option<[i32; 4]> {
+        if let Some(i) = self.free.iter().position(|slot| w <= slot[2] && h <= slot[3]) {
+            let [x, y, slot_w, slot_h] = self.free.swap_remove(i);
+            // The rest of a wider slot stays free
+            if slot_w > w {
+                self.free.push([x + w, y, slot_w - w, slot_h]);
+            }
+            return Some([x, y, w, slot_h]);
+        }
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| h <= shelf.h && shelf.x + w <= PAGE_SIZE) {
+            let slot = [shelf.x, shelf.y, w, shelf.h];
+            shelf.x += w;
+            return Some(slot);
+        }
+        let y = self.shelves.last().map_or(0, |shelf| shelf.y + shelf.h);
+        if y + h > PAGE_SIZE {
+            return None;
+        }
+        self.shelves.push(Shelf { y, h, x: w });
+        Some([0, y, w, h])
+    }
+
+    // Slots are as tall as their shelf, so free neighbors on one join into a wider
+    // slot. One that reaches the shelf's end goes back to the shelf.
+    fn free_slot(&mut self, mut slot: [i32; 4]) {
+        while let Some(i) = self.free.iter().position(|other| other[1] == slot[1] && other[3] == slot[3]
+            && (other[0] + other[2] == slot[0] || slot[0] + slot[2] == other[0]))
+        {
+            let other = self.free.swap_remove(i);
+            slot = [slot[0].min(other[0]), slot[1], slot[2] + other[2], slot[3]];
+        }
+        match self.shelves.iter_mut().find(|shelf| shelf.y == slot[1] && shelf.x == slot[0] + slot[2]) {
+            Some(shelf) => shelf.x = slot[0],
+            None => self.free.push(slot),
+        }
+    }
+}
+
+pub struct AtlasPages {
+    pages: Vec<Page>,
+    entries: HashMap<u32, Entry>,
+    next_entry: u32,
+}
+
+impl AtlasPages {
+    pub fn new() -> AtlasPages {
+        AtlasPages { pages: Vec::new(), entries: HashMap::new(), next_entry: FIRST_ENTRY_ID }
+    }
+
+    pub fn is_entry(id: u32) -> bool {
+        id >= FIRST_ENTRY_ID
+    }
+
+    // Finds room for a w*h entry, calling `new_page` for another page's image id
+    // when none of the current pages have any. None if it's too big for a page.
+    pub fn add<F: FnOnce() -> u32>(&mut self, w: i32, h: i32, new_page: F) -> Option<(u32, Placement)> {
+        let (slot_w, slot_h) = (w + PADDING, h + PADDING);
+        if w <= 0 || h <= 0 || slot_w > PAGE_SIZE || slot_h > PAGE_SIZE {
+            return None;
+        }
+        let found = self.pages.iter_mut().find_map(|page| {
+            let slot = page.find_slot(slot_w, slot_h)?;
+            page.entries += 1;
+            Some((page.id, slot))
+        });
+        let (page, slot) = match found {
+            Some(found) => found,
+            None => {
+                let mut page = Page {
+                    id: new_page(),
+                    pixels: vec![0; (PAGE_SIZE * PAGE_SIZE * 4) as usize],
+                    shelves: Vec::new(),
+                    free: Vec::new(),
+                    entries: 0,
+                };
+                let slot = page.find_slot(slot_w, slot_h).unwrap();
+                page.entries = 1;
+                let id = page.id;
+                self.pages.push(page);
+                (id, slot)
+            },
+        };
+        let placement = Placement { page, x: slot[0], y: slot[1], w, h };
+        let id = self.next_entry;
+        self.next_entry += 1;
+        self.entries.insert(id, Entry { placement, slot });
+        Some((id, placement))
+    }
+
+    pub fn get(&self, id: u32) -> Option<Placement> {
+        self.entries.get(&id).map(|entry| entry.placement)
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let entry = match self.entries.remove(&id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if let Some(page) = self.pages.iter_mut().find(|page| page.id == entry.placement.page) {
+            page.free_slot(entry.slot);
+            page.entries -= 1;
+        }
+        true
+    }
+
+    // Drops pages left without entries, returning their image ids for freeing
+    pub fn take_empty_pages(&mut self) -> Vec<u32> {
+        let empty = self.pages.iter().filter(|page| page.entries == 0).map(|page| page.id).collect();
+        self.pages.retain(|page| page.entries > 0);
+        empty
+    }
+
+    // Copies an entry's pixels, in the guest format, into its page. Returns the
+    // page's pixels and the region of them to upload.
+    pub fn write(&mut self, id: u32, pixels: &[u8]) -> Option<(&[u8], Placement)> {
+        let placement = self.get(id)?;
+        let page = self.pages.iter_mut().find(|page| page.id == placement.page)?;
+        let row = (placement.w * 4) as usize;
+        for (y, src) in pixels.chunks_exact(row).enumerate() {
+            let start = (((placement.y + y as i32) * PAGE_SIZE + placement.x) * 4) as usize;
+            page.pixels[start..start + row].copy_from_slice(src);
+        }
+        Some((&page.pixels, placement))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pages get image ids in order, from 1
+    fn add(atlas: &mut AtlasPages, w: i32, h: i32) -> (u32, Placement) {
+        let next_page = atlas.pages.len() as u32 + 1;
+        atlas.add(w, h, || next_page).unwrap()
+    }
+
+    fn position((_, placement): (u32, Placement)) -> (u32, i32, i32) {
+        (placement.page, placement.x, placement.y)
+    }
+
+    #[test]
+    fn packs_into_shelves() {
+        let mut atlas = AtlasPages::new();
+        assert_eq!(position(add(&mut atlas, 10, 20)), (1, 0, 0));
+        // Same shelf, past the first entry and its padding
+        assert_eq!(position(add(&mut atlas, 30, 5)), (1, 11, 0));
+        // Too tall for the shelf, so a new one above it
+        assert_eq!(position(add(&mut atlas, 10, 40)), (1, 0, 21));
+        // Back to the first shelf, which has room
+        assert_eq!(position(add(&mut atlas, 8, 8)), (1, 42, 0));
+        // Off the end of the first shelf
+        assert_eq!(position(add(&mut atlas, PAGE_SIZE - 40, 8)), (1, 11, 21));
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let mut atlas = AtlasPages::new();
+        let (first, _) = add(&mut atlas, 16, 16);
+        add(&mut atlas, 16, 16);
+        assert!(atlas.remove(first));
+        assert!(!atlas.remove(first));
+        assert!(atlas.get(first).is_none());
+        // Too big for the freed slot, so it goes after
+        assert_eq!(position(add(&mut atlas, 20, 16)), (1, 34, 0));
+        // Smaller fits
+        let (id, placement) = add(&mut atlas, 12, 10);
+        assert_ne!(id, first);
+        assert_eq!(position((id, placement)), (1, 0, 0));
+        assert_eq!((placement.w, placement.h), (12, 10));
+    }
+
+    #[test]
+    fn merges_adjacent_free_slots() {
+        let mut atlas = AtlasPages::new();
+        let ids: Vec<u32> = (0..4).map(|_| add(&mut atlas, 9, 9).0).collect();
+        // Freed out of order, the middle two join into one 20 wide slot
+        assert!(atlas.remove(ids[2]));
+        assert!(atlas.remove(ids[1]));
+        assert_eq!(position(add(&mut atlas, 19, 9)), (1, 10, 0));
+        // Which left nothing behind
+        assert_eq!(position(add(&mut atlas, 4, 4)), (1, 40, 0));
+    }
+
+    #[test]
+    fn splits_wide_free_slots() {
+        let mut atlas = AtlasPages::new();
+        let (wide, _) = add(&mut atlas, 29, 9);
+        add(&mut atlas, 9, 9);
+        assert!(atlas.remove(wide));
+        assert_eq!(position(add(&mut atlas, 9, 9)), (1, 0, 0));
+        assert_eq!(position(add(&mut atlas, 9, 9)), (1, 10, 0));
+        assert_eq!(position(add(&mut atlas, 9, 9)), (1, 20, 0));
+        assert_eq!(position(add(&mut atlas, 9, 9)), (1, 40, 0));
+    }
+
+    #[test]
+    fn gives_trailing_space_back_to_the_shelf() {
+        let mut atlas = AtlasPages::new();
+        let (small, _) = add(&mut atlas, 9, 9);
+        let (last, _) = add(&mut atlas, 9, 9);
+        assert!(atlas.remove(last));
+        assert!(atlas.remove(small));
+        // The whole shelf is open again, even for something wider than both
+        assert_eq!(position(add(&mut atlas, PAGE_SIZE - 1, 9)), (1, 0, 0));
+    }
+
+    #[test]
+    fn drops_empty_pages() {
+        let mut atlas = AtlasPages::new();
+        let half = PAGE_SIZE / 2 - 1;
+        let first: Vec<u32> = (0..4).map(|_| add(&mut atlas, half, half).0).collect();
+        let (second, _) = add(&mut atlas, half, half);
+        for &id in &first[1..] {
+            atlas.remove(id);
+        }
+        assert!(atlas.take_empty_pages().is_empty());
+        atlas.remove(first[0]);
+        assert_eq!(atlas.take_empty_pages(), vec![1]);
+        assert!(atlas.take_empty_pages().is_empty());
+        // Entries on other pages are untouched
+        assert_eq!(atlas.get(second).map(|placement| placement.page), Some(2));
+        atlas.remove(second);
+        assert_eq!(atlas.take_empty_pages(), vec![2]);
+    }
+
+    #[test]
+    fn rejects_what_cant_fit_a_page() {
+        let mut atlas = AtlasPages::new();
+        let mut called = false;
+        // The padding has to fit too
+        assert!(atlas.add(PAGE_SIZE, 1, || { called = true; 1 }).is_none());
+        assert!(atlas.add(1, PAGE_SIZE, || { called = true; 1 }).is_none());
+        assert!(atlas.add(0, 1, || { called = true; 1 }).is_none());
+        assert!(atlas.add(1, -1, || { called = true; 1 }).is_none());
+        assert!(!called);
+        assert_eq!(position(add(&mut atlas, PAGE_SIZE - 1, PAGE_SIZE - 1)), (1, 0, 0));
+    }
+
+    #[test]
+    fn overflows_onto_a_new_page() {
+        let mut atlas = AtlasPages::new();
+        let half = PAGE_SIZE / 2 - 1;
+        for &(x, y) in &[(0, 0), (half + 1, 0), (0, half + 1), (half + 1, half + 1)] {
+            assert_eq!(position(add(&mut atlas, half, half)), (1, x, y));
+        }
+        assert_eq!(position(add(&mut atlas, half, half)), (2, 0, 0));
+        // The first page is full, even for something small
+        assert_eq!(position(add(&mut atlas, 4, 4)), (2, half + 1, 0));
+    }
+
+    #[test]
+    fn maps_source_rects_into_the_page() {
+        let placement = Placement { page: 1, x: 256, y: 512, w: 128, h: 64 };
+        assert_eq!(placement.source_rect([0.0, 0.0, 1.0, 1.0]), [0.25, 0.5, 0.125, 0.0625]);
+        // The right half, bottom quarter of the entry
+        assert_eq!(placement.source_rect([0.5, 0.0, 0.5, 0.25]), [0.3125, 0.5, 0.0625, 0.015625]);
+    }
+}
@@ -0,0 +1,579 @@
+// Retained render commands
+//
+// Draw imports don't touch the backend; they record into the frame's command
+// list, which runs once every component has updated. Recording is per pass,
+// one for the screen and one for each beginTarget. Within a pass, commands are
+// sorted by z-layer (the caller's, from its viewport) then layer (from
+// setLayer), and draws sharing a material are batched together.
+
+use std::{
+    fmt::{self, Write},
+    rc::Rc,
+};
+
+use crate::math::Mat4;
+use crate::shapes::Vertex;
+use super::Renderer;
+
+// One textured quad: the unit quad through `transform`, sampling `source_rect`
+// (in UVs) and multiplied by `tint` (straight alpha)
+#[derive(Clone, Copy)]
+pub struct Quad {
+    pub transform: Mat4,
+    pub source_rect: [f32; 4],
+    pub tint: [f32; 4],
+}
+
+// A value for a custom shader's uniform, typed by what the guest set it with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Uniform {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Int(i32),
+}
+pub type Uniforms = Vec<(String, Uniform)>;
+
+// A custom shader, with its uniforms as they were when the draw was recorded
+#[derive(Clone)]
+pub struct ShaderUse {
+    pub id: u32,
+    pub uniforms: Rc<Uniforms>,
+}
+impl PartialEq for ShaderUse {
+    // Uniforms are copied on write, so sharing them means they're unchanged
+    fn eq(&self, other: &ShaderUse) -> bool {
+        self.id == other.id && Rc::ptr_eq(&self.uniforms, &other.uniforms)
+    }
+}
+
+// A rect of the screen, in pixel space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+enum Draw {
+    Quad { id: u32, checkerboard: bool, shader: Option<ShaderUse>, quad: Quad },
+    Triangles(Vec<Vertex>),
+    // Only recorded for clears within a viewport, which just cover the viewport
+    Clear([f32; 4]),
+}
+
+struct Command {
+    z: i32,
+    layer: i32,
+    // Draws to the screen from within a viewport are clipped to it
+    clip: Option<Viewport>,
+    draw: Draw,
+    // Pixel-space bounding box, x0, y0, x1, y1
+    bounds: [f32; 4],
+}
+
+// Everything drawn into one target, or the screen for None
+pub struct Pass {
+    target: Option<u32>,
+    // Applied before the pass draws. Clearing drops whatever was recorded before it.
+    clear: Option<[f32; 4]>,
+    commands: Vec<Command>,
+}
+
+// Consecutive draws to run with a single bind
+struct Batch {
+    z: i32,
+    layer: i32,
+    clip: Option<Viewport>,
+    bounds: [f32; 4],
+    draws: Draws,
+}
+enum Draws {
+    Quads { id: u32, checkerboard: bool, shader: Option<ShaderUse>, quads: Vec<Quad> },
+    Triangles(Vec<Vertex>),
+    Clear([f32; 4]),
+}
+
+// A post pass: a custom shader run over the screen, or just the viewport it was added from
+struct PostPass {
+    shader: ShaderUse,
+    clip: Option<Viewport>,
+}
+
+// Everything recorded in a frame, ready to run
+pub struct Frame {
+    passes: Vec<Pass>,
+    post_passes: Vec<PostPass>,
+}
+
+pub struct CommandList {
+    // Passes that have ended, in the order they run
+    finished: Vec<Pass>,
+    // Run over the screen once it's drawn, in order
+    post_passes: Vec<PostPass>,
+    // Passes being recorded into: the screen's, then any targets, innermost last
+    open: Vec<Pass>,
+    // Set by setLayer, higher layers draw on top
+    layer: i32,
+    // Viewport and z-layer of the component being called into
+    viewport: Option<Viewport>,
+    z: i32,
+}
+
+impl CommandList {
+    pub fn new() -> CommandList {
+        CommandList {
+            finished: Vec::new(),
+            post_passes: Vec::new(),
+            open: vec![Pass::new(None)],
+            layer: 0,
+            viewport: None,
+            z: 0,
+        }
+    }
+
+    // Everything recorded until the next call is from a component drawing into
+    // `viewport`, on z-layer `z`. Its layer starts over at 0.
+    pub fn set_scope(&mut self, viewport: Option<Viewport>, z: i32) {
+        self.viewport = viewport;
+        self.z = z;
+        self.layer = 0;
+    }
+
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    pub fn quad(&mut self, id: u32, quad: Quad, checkerboard: bool, shader: Option<ShaderUse>) {
+        self.push(Draw::Quad { id, checkerboard, shader, quad });
+    }
+
+    pub fn post_pass(&mut self, shader: ShaderUse) {
+        // Always over the screen, even from inside a target
+        let clip = self.viewport;
+        self.post_passes.push(PostPass { shader, clip });
+    }
+
+    pub fn triangles(&mut self, vertices: Vec<Vertex>) {
+        if vertices.is_empty() {
+            return;
+        }
+        self.push(Draw::Triangles(vertices));
+    }
+
+    pub fn clear(&mut self, color: [f32; 4]) {
+        if self.screen_clip().is_some() {
+            // Other components share the screen, so this only covers the viewport
+            self.push(Draw::Clear(color));
+            return;
+        }
+        let pass = self.current();
+        pass.commands.clear();
+        pass.clear = Some(color);
+    }
+
+    pub fn begin_target(&mut self, id: u32) {
+        self.open.push(Pass::new(Some(id)));
+    }
+
+    // The target drawing continues into, or None if no target was open
+    pub fn end_target(&mut self) -> Option<Option<u32>> {
+        if self.open.len() == 1 {
+            return None;
+        }
+        let pass = self.open.pop().unwrap();
+        self.finished.push(pass);
+        Some(self.current().target)
+    }
+
+    // Drops everything drawn into a target that's been freed
+    pub fn forget_target(&mut self, id: u32) {
+        self.finished.retain(|pass| pass.target != Some(id));
+        self.open.retain(|pass| pass.target != Some(id));
+    }
+
+    // Targets still being recorded into, innermost last
+    pub fn open_targets(&self) -> Vec<u32> {
+        self.open.iter().filter_map(|pass| pass.target).collect()
+    }
+
+    // Ends every pass, leaving the list empty for the next frame. Targets
+    // run before the screen, in the order they ended, so a target's contents
+    // are the last thing drawn into it this frame.
+    pub fn take(&mut self) -> Frame {
+        // The screen's pass is the bottom of the stack, so it comes out last
+        let mut passes = std::mem::replace(&mut self.finished, Vec::new());
+        passes.extend(self.open.drain(..).rev());
+        let post_passes = std::mem::replace(&mut self.post_passes, Vec::new());
+        *self = CommandList::new();
+        Frame { passes, post_passes }
+    }
+
+    fn current(&mut self) -> &mut Pass {
+        self.open.last_mut().unwrap()
+    }
+
+    // Targets have their own pixel space, viewports only apply on the screen
+    fn screen_clip(&self) -> Option<Viewport> {
+        if self.open.len() == 1 { self.viewport } else { None }
+    }
+
+    fn push(&mut self, mut draw: Draw) {
+        let clip = self.screen_clip();
+        let mut bounds = draw.bounds();
+        if let Some(clip) = clip {
+            let (x, y) = (clip.x as f32, clip.y as f32);
+            draw.translate(x, y);
+            bounds = intersect(draw.bounds(), [x, y, x + clip.w as f32, y + clip.h as f32]);
+        }
+        let (z, layer) = (self.z, self.layer);
+        self.current().commands.push(Command { z, layer, clip, draw, bounds });
+    }
+}
+
+impl Draw {
+    fn translate(&mut self, dx: f32, dy: f32) {
+        match self {
+            Draw::Quad { quad, .. } => {
+                quad.transform[12] += dx;
+                quad.transform[13] += dy;
+            },
+            Draw::Triangles(vertices) => {
+                for v in vertices {
+                    v.x += dx;
+                    v.y += dy;
+                }
+            },
+            Draw::Clear(_) => {},
+        }
+    }
+
+    // Pixel-space bounding box
+    fn bounds(&self) -> [f32; 4] {
+        match self {
+            Draw::Quad { quad, .. } => quad_bounds(&quad.transform),
+            Draw::Triangles(vertices) => {
+                let mut bounds = [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY];
+                for v in vertices {
+                    bounds = [bounds[0].min(v.x), bounds[1].min(v.y), bounds[2].max(v.x), bounds[3].max(v.y)];
+                }
+                bounds
+            },
+            Draw::Clear(_) => [f32::NEG_INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::INFINITY],
+        }
+    }
+}
+
+impl Pass {
+    fn new(target: Option<u32>) -> Pass {
+        Pass { target, clear: None, commands: Vec::new() }
+    }
+
+    // Sorts by z-layer then layer, keeping recorded order within one. A draw
+    // can join an earlier batch of its material and clip when nothing drawn
+    // since overlaps it, since then moving it earlier can't change what ends
+    // up on top.
+    fn batches(&self) -> Vec<Batch> {
+        let mut commands: Vec<&Command> = self.commands.iter().collect();
+        commands.sort_by_key(|command| (command.z, command.layer));
+        let mut batches: Vec<Batch> = Vec::new();
+        'commands: for command in commands {
+            for batch in batches.iter_mut().rev() {
+                if (batch.z, batch.layer) != (command.z, command.layer) {
+                    break;
+                }
+                if batch.clip == command.clip && batch.draws.add(&command.draw) {
+                    batch.bounds = union(batch.bounds, command.bounds);
+                    continue 'commands;
+                }
+                if overlaps(batch.bounds, command.bounds) {
+                    break;
+                }
+            }
+            let draws = match &command.draw {
+                Draw::Quad { id, checkerboard, shader, quad } => Draws::Quads {
+                    id: *id,
+                    checkerboard: *checkerboard,
+                    shader: shader.clone(),
+                    quads: vec![*quad],
+                },
+                Draw::Triangles(vertices) => Draws::Triangles(vertices.clone()),
+                Draw::Clear(color) => Draws::Clear(*color),
+            };
+            batches.push(Batch { z: command.z, layer: command.layer, clip: command.clip, bounds: command.bounds, draws });
+        }
+        batches
+    }
+}
+
+impl Draws {
+    // Adds a draw with the same material, false for anything else
+    fn add(&mut self, draw: &Draw) -> bool {
+        match (self, draw) {
+            (Draws::Quads { id, checkerboard, shader, quads }, Draw::Quad { id: quad_id, checkerboard: quad_checkerboard, shader: quad_shader, quad })
+                if id == quad_id && checkerboard == quad_checkerboard && shader == quad_shader => {
+                quads.push(*quad);
+                true
+            },
+            (Draws::Triangles(batched), Draw::Triangles(vertices)) => {
+                batched.extend_from_slice(vertices);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+// Runs a frame's passes, then its post passes, leaving the screen bound.
+// Passes into targets that have been freed or re-uploaded since are skipped.
+pub fn execute(frame: &Frame, renderer: &mut dyn Renderer) {
+    for pass in &frame.passes {
+        if !renderer.set_target(pass.target) {
+            continue;
+        }
+        renderer.set_clip(None);
+        if let Some(color) = pass.clear {
+            renderer.clear(color);
+        }
+        for batch in pass.batches() {
+            renderer.set_clip(batch.clip);
+            match &batch.draws {
+                Draws::Quads { id, checkerboard, shader, quads } =>
+                    renderer.draw_quads(*id, quads, *checkerboard, shader.as_ref()),
+                Draws::Triangles(vertices) => renderer.draw_triangles(vertices),
+                Draws::Clear(color) => renderer.clear(*color),
+            }
+        }
+    }
+    renderer.set_target(None);
+    for post in &frame.post_passes {
+        renderer.set_clip(post.clip);
+        renderer.post_pass(&post.shader);
+    }
+    renderer.set_clip(None);
+}
+
+// A text dump of a frame's passes, as recorded, one command per line. Floats
+// print exactly, so dumps of the same frame compare equal.
+pub fn serialize(frame: &Frame) -> String {
+    let mut out = String::new();
+    for pass in &frame.passes {
+        write_pass(&mut out, pass).unwrap();
+    }
+    for post in &frame.post_passes {
+        write!(out, "post ").unwrap();
+        if let Some(clip) = post.clip {
+            write!(out, "clip [{} {} {} {}] ", clip.x, clip.y, clip.w, clip.h).unwrap();
+        }
+        write_shader(&mut out, &post.shader).unwrap();
+        writeln!(out).unwrap();
+    }
+    out
+}
+
+fn write_pass(out: &mut String, pass: &Pass) -> fmt::Result {
+    match pass.target {
+        Some(id) => write!(out, "pass target {}", id)?,
+        None => write!(out, "pass screen")?,
+    }
+    if let Some(color) = pass.clear {
+        write!(out, " clear {}", floats(&color))?;
+    }
+    writeln!(out)?;
+    for command in &pass.commands {
+        write!(out, "  z {} layer {} ", command.z, command.layer)?;
+        if let Some(clip) = command.clip {
+            write!(out, "clip [{} {} {} {}] ", clip.x, clip.y, clip.w, clip.h)?;
+        }
+        match &command.draw {
+            Draw::Quad { id, checkerboard, shader, quad } => {
+                write!(out, "quad image {} checkerboard {} ", id, checkerboard)?;
+                if let Some(shader) = shader {
+                    write_shader(out, shader)?;
+                    write!(out, " ")?;
+                }
+                writeln!(out, "transform {} source {} tint {}",
+                    floats(&quad.transform), floats(&quad.source_rect), floats(&quad.tint))?
+            },
+            Draw::Triangles(vertices) => {
+                let values: Vec<f32> = vertices.iter()
+                    .flat_map(|v| vec![v.x, v.y, v.color[0], v.color[1], v.color[2], v.color[3]])
+                    .collect();
+                writeln!(out, "triangles {} {}", vertices.len() / 3, floats(&values))?
+            },
+            Draw::Clear(color) => writeln!(out, "clear {}", floats(color))?,
+        }
+    }
+    Ok(())
+}
+
+fn write_shader(out: &mut String, shader: &ShaderUse) -> fmt::Result {
+    write!(out, "shader {}", shader.id)?;
+    for (name, value) in shader.uniforms.iter() {
+        match value {
+            Uniform::Float(v) => write!(out, " {}={}", name, v)?,
+            Uniform::Vec2(v) => write!(out, " {}={}", name, floats(v))?,
+            Uniform::Vec3(v) => write!(out, " {}={}", name, floats(v))?,
+            Uniform::Vec4(v) => write!(out, " {}={}", name, floats(v))?,
+            Uniform::Int(v) => write!(out, " {}={}i", name, v)?,
+        }
+    }
+    Ok(())
+}
+
+fn floats(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(" "))
+}
+
+// Where the unit quad lands through `transform`
+fn quad_bounds(transform: &Mat4) -> [f32; 4] {
+    let (a, b, c, d, tx, ty) = (transform[0], transform[1], transform[4], transform[5], transform[12], transform[13]);
+    let mut bounds = [f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY];
+    for &(lx, ly) in &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+        let (x, y) = (a * lx + c * ly + tx, b * lx + d * ly + ty);
+        bounds = [bounds[0].min(x), bounds[1].min(y), bounds[2].max(x), bounds[3].max(y)];
+    }
+    bounds
+}
+
+fn overlaps(a: [f32; 4], b: [f32; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+fn intersect(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0].max(b[0]), a[1].max(b[1]), a[2].min(b[2]), a[3].min(b[3])]
+}
+
+fn union(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    fn quad(x: f32, y: f32) -> Quad {
+        Quad { transform: math::rect_transform(x, y, 4.0, 4.0, 1.0, 0.0), source_rect: [0.0, 0.0, 1.0, 1.0], tint: [1.0; 4] }
+    }
+
+    // The screen's batches, as "image x count" for quads
+    fn batches(frame: &Frame) -> Vec<String> {
+        let screen = frame.passes.last().unwrap();
+        assert_eq!(screen.target, None);
+        screen.batches().iter().map(|batch| match &batch.draws {
+            Draws::Quads { id, quads, .. } => format!("{} x{}", id, quads.len()),
+            Draws::Triangles(vertices) => format!("triangles x{}", vertices.len() / 3),
+            Draws::Clear(_) => "clear".to_string(),
+        }).collect()
+    }
+
+    #[test]
+    fn sorts_by_layer_keeping_order() {
+        let mut list = CommandList::new();
+        list.set_layer(1);
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.set_layer(0);
+        list.quad(2, quad(0.0, 0.0), false, None);
+        list.set_layer(1);
+        list.quad(3, quad(0.0, 0.0), false, None);
+        list.set_layer(0);
+        list.quad(4, quad(0.0, 0.0), false, None);
+        // A higher z-layer goes on top whatever its layer
+        list.set_scope(None, 1);
+        list.set_layer(-5);
+        list.quad(5, quad(0.0, 0.0), false, None);
+        list.set_scope(None, 0);
+        list.quad(6, quad(0.0, 0.0), false, None);
+        assert_eq!(batches(&list.take()), ["2 x1", "4 x1", "6 x1", "1 x1", "3 x1", "5 x1"]);
+    }
+
+    #[test]
+    fn joins_only_past_what_it_doesnt_overlap() {
+        let mut list = CommandList::new();
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.quad(2, quad(2.0, 2.0), false, None);
+        // Under 2, so it can't move before it
+        list.quad(1, quad(0.0, 0.0), false, None);
+        assert_eq!(batches(&list.take()), ["1 x1", "2 x1", "1 x1"]);
+
+        let mut list = CommandList::new();
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.quad(2, quad(10.0, 0.0), false, None);
+        list.quad(1, quad(20.0, 0.0), false, None);
+        assert_eq!(batches(&list.take()), ["1 x2", "2 x1"]);
+
+        // Nor across a different checkerboard or clip
+        let mut list = CommandList::new();
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.quad(1, quad(10.0, 0.0), true, None);
+        list.set_scope(Some(Viewport { x: 0, y: 0, w: 100, h: 100 }), 0);
+        list.quad(1, quad(20.0, 0.0), false, None);
+        assert_eq!(batches(&list.take()), ["1 x1", "1 x1", "1 x1"]);
+    }
+
+    #[test]
+    fn clear_drops_earlier_commands() {
+        let mut list = CommandList::new();
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.clear([0.0, 0.0, 0.0, 1.0]);
+        list.quad(2, quad(0.0, 0.0), false, None);
+        let frame = list.take();
+        assert_eq!(frame.passes[0].clear, Some([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(batches(&frame), ["2 x1"]);
+
+        // Within a viewport it only covers the viewport, after what's there
+        let mut list = CommandList::new();
+        list.quad(1, quad(0.0, 0.0), false, None);
+        list.set_scope(Some(Viewport { x: 0, y: 0, w: 10, h: 10 }), 0);
+        list.clear([0.0, 0.0, 0.0, 1.0]);
+        let frame = list.take();
+        assert_eq!(frame.passes[0].clear, None);
+        assert_eq!(batches(&frame), ["1 x1", "clear"]);
+    }
+
+    #[test]
+    fn targets_run_in_the_order_they_end() {
+        let mut list = CommandList::new();
+        list.begin_target(1);
+        list.begin_target(2);
+        assert_eq!(list.open_targets(), [1, 2]);
+        assert_eq!(list.end_target(), Some(Some(1)));
+        assert_eq!(list.end_target(), Some(None));
+        assert_eq!(list.end_target(), None);
+        // Left open, so it ends with the frame, ahead of the screen
+        list.begin_target(3);
+        let frame = list.take();
+        let targets: Vec<Option<u32>> = frame.passes.iter().map(|pass| pass.target).collect();
+        assert_eq!(targets, [Some(2), Some(1), Some(3), None]);
+        assert!(list.open_targets().is_empty());
+    }
+
+    #[test]
+    fn serializes_a_frame() {
+        let mut list = CommandList::new();
+        list.clear([0.0, 0.0, 0.0, 1.0]);
+        list.set_layer(1);
+        list.quad(5, quad(2.0, 0.0), false, None);
+        list.begin_target(7);
+        list.triangles(vec![
+            Vertex { x: 0.0, y: 0.0, color: [1.0; 4] },
+            Vertex { x: 1.0, y: 0.0, color: [1.0; 4] },
+            Vertex { x: 0.0, y: 1.0, color: [1.0; 4] },
+        ]);
+        list.end_target();
+        list.set_scope(Some(Viewport { x: 1, y: 2, w: 3, h: 4 }), 0);
+        let uniforms = Rc::new(vec![("Amount".to_string(), Uniform::Float(0.5)), ("Steps".to_string(), Uniform::Int(3))]);
+        list.post_pass(ShaderUse { id: 9, uniforms });
+        assert_eq!(serialize(&list.take()), "\
+pass target 7
+  z 0 layer 1 triangles 1 [0 0 1 1 1 1 1 0 1 1 1 1 0 1 1 1 1 1]
+pass screen clear [0 0 0 1]
+  z 0 layer 1 quad image 5 checkerboard false transform [4 0 0 0 -0 4 0 0 0 0 1 0 4 2 0 1] source [0 0 1 1] tint [1 1 1 1]
+post clip [1 2 3 4] shader 9 Amount=0.5 Steps=3i
+");
+    }
+}
@@ -0,0 +1,1066 @@
+// Render imports, and the backends that carry them out
+//
+// Imports don't get to see a backend directly. Draws are recorded into the
+// frame's command list, which runs on the one installed with `init` after
+// every component has updated. Everything shared between backends (upload
+// batching, fonts, commands) lives here; backends only draw.
+
+use sdl2::ttf::Sdl2TtfContext;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::{Rc, Weak},
+};
+
+use wasmtime::*;
+
+use crate::capture;
+use crate::component::{Component, ImportModule};
+use crate::decode;
+use crate::math::{self, Mat4};
+use crate::shapes::{ShapeBatch, Vertex};
+use crate::text::{self, Atlas};
+
+mod atlas;
+mod commands;
+mod opengl;
+mod shader;
+mod software;
+use atlas::AtlasPages;
+use commands::{CommandList, Quad, ShaderUse, Uniform, Uniforms};
+pub use commands::Viewport;
+pub use opengl::GlRenderer;
+pub use software::SoftwareRenderer;
+
+// Pixel format contract between guests and the host:
+// An image is w*h pixels, row-major, with row 0 at the bottom (matching the
+// bottom-left origin of pixel space). Each pixel is 4 bytes, in r, g, b, a
+// order, with straight (not premultiplied) alpha. Where a pixel crosses the
+// ABI as a single s32, r is the low byte, i.e. 0xAABBGGRR.
+// The host premultiplies at draw time, and blends premultiplied.
+
+// How an image is filtered when it's drawn bigger or smaller than it is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    Nearest,
+    Linear,
+    // Linear, and blended between mip levels when drawn smaller, for zoomed-out views
+    Mipmapped,
+}
+impl Sampling {
+    // As guests pass it to setImageSampling
+    fn from_i32(mode: i32) -> Option<Sampling> {
+        match mode {
+            0 => Some(Sampling::Nearest),
+            1 => Some(Sampling::Linear),
+            2 => Some(Sampling::Mipmapped),
+            _ => None,
+        }
+    }
+}
+
+// A render backend. Positions are in pixel space, origin at the bottom-left,
+// and image ids are handed out by the backend itself.
+pub trait Renderer {
+    // Size of pixel space, which follows the window's logical size
+    fn size(&self) -> (u32, u32);
+    // Size of the framebuffer, which is bigger than `size()` on high-DPI displays
+    fn drawable_size(&self) -> (u32, u32);
+    fn begin_frame(&mut self, clear_color: [f32; 4]);
+    fn end_frame(&mut self);
+
+    fn alloc_image(&mut self) -> u32;
+    fn free_image(&mut self, id: u32);
+    // Replaces the whole image, keeping its sampling
+    fn upload_image(&mut self, id: u32, pixels: &[u8], w: i32, h: i32);
+    // Copies the half-open region [x0, x1) x [y0, y1) of a `tex_w` pixel wide
+    // image into the same spot of an image that's already been uploaded
+    fn upload_region(&mut self, id: u32, pixels: &[u8], tex_w: i32, x0: i32, y0: i32, x1: i32, y1: i32);
+    // Images start out Nearest. Mipmaps are kept up to date through uploads and draws into targets.
+    fn set_sampling(&mut self, id: u32, sampling: Sampling);
+
+    // An image that can be drawn into, starting out transparent. Its contents
+    // are premultiplied; uploading into it makes it an ordinary image again.
+    fn alloc_target(&mut self, w: i32, h: i32) -> u32;
+    // Redirects draws into a render target, or back to the screen for None.
+    // Pixel space follows, covering the target. False if `target` isn't one.
+    fn set_target(&mut self, target: Option<u32>) -> bool;
+    // Fills the current target, straight alpha
+    fn clear(&mut self, color: [f32; 4]);
+    // Limits draws to a pixel-space rect of the screen, or lifts the limit for None
+    fn set_clip(&mut self, clip: Option<Viewport>);
+
+    // Whether `id` was made with alloc_target, and is still a target
+    fn is_target(&self, id: u32) -> bool;
+    // Builds a component's custom fragment shader, see effect_prelude.frag for
+    // what it's given. Errors carry the compiler's log.
+    fn create_shader(&mut self, source: &str) -> Result<u32, String>;
+    fn free_shader(&mut self, id: u32);
+    // Draws quads of one image, in order, through a custom shader if there is one
+    fn draw_quads(&mut self, id: u32, quads: &[Quad], checkerboard: bool, shader: Option<&ShaderUse>);
+    // Three vertices per triangle, colors premultiplied
+    fn draw_triangles(&mut self, vertices: &[Vertex]);
+    // Runs a custom shader over everything drawn to the screen so far, within the clip
+    fn post_pass(&mut self, shader: &ShaderUse);
+
+    // What's been drawn so far this frame, `drawable_size()` pixels in RGBA, rows bottom-up
+    fn read_pixels(&mut self) -> Vec<u8>;
+    // An image's size and contents, in the guest pixel format. None if there's no such image.
+    fn read_image(&mut self, id: u32) -> Option<(i32, i32, Vec<u8>)>;
+}
+
+thread_local! {
+    static RENDERER: RefCell<Option<Box<dyn Renderer>>> = RefCell::new(None);
+}
+
+// Per-frame state the imports draw with
+#[derive(Default)]
+struct FrameState {
+    // Set while a component with its own viewport is being called into
+    viewport: Option<Viewport>,
+    // Set by setCheckerboard, applies to image draws only
+    checkerboard_enabled: bool,
+    // Set by setShader, also only for image draws
+    shader: Option<u32>,
+    // Where to save this frame once it's drawn
+    captures: Vec<PathBuf>,
+    // Where to save this frame's command list
+    command_dumps: Vec<PathBuf>,
+    // Images freed this frame. They're only freed on the backend once the
+    // frame's commands have run, since those may still draw them.
+    freed: Vec<u32>,
+    // Likewise for custom shaders
+    freed_shaders: Vec<u32>,
+}
+thread_local! {
+    static FRAME: RefCell<FrameState> = RefCell::new(FrameState::default());
+}
+impl FrameState {
+    // Moves on to another caller, who starts out without the last one's
+    // checkerboard or shader
+    fn set_scope(&mut self, viewport: Option<Viewport>) {
+        self.viewport = viewport;
+        self.checkerboard_enabled = false;
+        self.shader = None;
+    }
+}
+
+// A dirty region of an image waiting to be uploaded from guest memory
+struct PendingUpload {
+    component: Weak<RefCell<Component>>,
+    image_ptr: i32,
+    tex_w: i32,
+    tex_h: i32,
+    // Half-open bounds of the dirty region
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+thread_local! {
+    static PENDING_UPLOADS: RefCell<HashMap<u32, PendingUpload>> = RefCell::new(HashMap::new());
+}
+
+// Largest image or target side, which every GL 3 driver supports as a texture and
+// which keeps the software renderer's byte offsets (w * h * 4) inside an i32
+const MAX_IMAGE_SIZE: i32 = 16384;
+
+// Sizes glyphs are rasterized at. Text bigger or smaller scales the nearest.
+const MIN_FONT_SIZE: f32 = 1.0;
+const MAX_FONT_SIZE: f32 = 256.0;
+// Rasterized atlases kept at once, beyond which the least recently used goes
+const MAX_FONT_ATLASES: usize = 8;
+
+// Glyph atlases, uploaded on first use
+struct Fonts {
+    // None for the bitmap font
+    ttf: Option<Sdl2TtfContext>,
+    // Keyed by rasterized pixel size, with 0 for the bitmap font
+    atlases: HashMap<u32, (Atlas, u32)>,
+    // Rasterized sizes, least recently used first
+    recent: Vec<u32>,
+    // Sizes that failed to rasterize, which use the bitmap font
+    failed: HashSet<u32>,
+}
+thread_local! {
+    static FONTS: RefCell<Fonts> = RefCell::new(Fonts {
+        ttf: None,
+        atlases: HashMap::new(),
+        recent: Vec::new(),
+        failed: HashSet::new(),
+    });
+}
+
+thread_local! {
+    static COMMANDS: RefCell<CommandList> = RefCell::new(CommandList::new());
+}
+
+thread_local! {
+    static ATLAS: RefCell<AtlasPages> = RefCell::new(AtlasPages::new());
+}
+
+thread_local! {
+    // Current uniforms of every custom shader. Draws keep the ones they were recorded with.
+    static SHADERS: RefCell<HashMap<u32, Rc<Uniforms>>> = RefCell::new(HashMap::new());
+}
+
+const FULL_SOURCE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+const NO_TINT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const CLEAR_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+// Installs the backend every render import draws with
+pub fn init(renderer: Box<dyn Renderer>) {
+    RENDERER.with(|r| *r.borrow_mut() = Some(renderer));
+    decode::init();
+
+    if std::path::Path::new(text::FONT_PATH).exists() {
+        match sdl2::ttf::init() {
+            Ok(ttf) => FONTS.with(|fonts| fonts.borrow_mut().ttf = Some(ttf)),
+            Err(e) => println!("Failed to initialize SDL_ttf, using bitmap font: {}", e),
+        }
+    } else {
+        println!("No font at {}, using bitmap font", text::FONT_PATH);
+    }
+}
+
+fn with_renderer<T, F: FnOnce(&mut dyn Renderer) -> T>(f: F) -> T {
+    RENDERER.with(|r| {
+        let mut r = r.borrow_mut();
+        f(r.as_mut().expect("Renderer not initialized").as_mut())
+    })
+}
+
+pub fn pre_update() {
+    FRAME.with(|frame| {
+        let mut frame = frame.borrow_mut();
+        frame.checkerboard_enabled = false;
+        frame.shader = None;
+    });
+}
+
+// Runs everything recorded this frame
+pub fn post_update() {
+    let unended = COMMANDS.with(|commands| commands.borrow().open_targets().len());
+    if unended > 0 {
+        println!("{} render target(s) never ended this frame", unended);
+    }
+    let commands = COMMANDS.with(|commands| commands.borrow_mut().take());
+    commit_uploads();
+    with_renderer(|r| {
+        r.begin_frame(CLEAR_COLOR);
+        commands::execute(&commands, r);
+    });
+    let dumps = FRAME.with(|frame| std::mem::replace(&mut frame.borrow_mut().command_dumps, Vec::new()));
+    for path in dumps {
+        if let Err(e) = capture::write_text(&path, &commands::serialize(&commands)) {
+            println!("Failed to save commands to {}: {}", path.display(), e);
+        }
+    }
+    let captures = FRAME.with(|frame| std::mem::replace(&mut frame.borrow_mut().captures, Vec::new()));
+    if !captures.is_empty() {
+        let ((w, h), pixels) = with_renderer(|r| (r.drawable_size(), r.read_pixels()));
+        for path in captures {
+            if let Err(e) = capture::write_png(&path, w, h, &pixels) {
+                println!("Failed to save frame to {}: {}", path.display(), e);
+            }
+        }
+    }
+    let (freed, freed_shaders) = FRAME.with(|frame| {
+        let mut frame = frame.borrow_mut();
+        (std::mem::replace(&mut frame.freed, Vec::new()), std::mem::replace(&mut frame.freed_shaders, Vec::new()))
+    });
+    with_renderer(|r| {
+        r.end_frame();
+        for tex_id in freed {
+            r.free_image(tex_id);
+        }
+        for shader_id in freed_shaders {
+            r.free_shader(shader_id);
+        }
+    });
+}
+
+// Current size of pixel space, for mapping window coordinates into it
+pub fn screen_size() -> (u32, u32) {
+    with_renderer(|r| r.size())
+}
+
+// Calls `f` with `viewport` as pixel space, drawing on z-layer `z`. Draws to the
+// screen are offset into the viewport and clipped to it, and the screen size
+// imports report its size. Higher z-layers draw on top. The checkerboard and
+// custom shader start out off, and go back off after.
+pub fn with_viewport<T, F: FnOnce() -> T>(viewport: Viewport, z: i32, f: F) -> T {
+    FRAME.with(|frame| frame.borrow_mut().set_scope(Some(viewport)));
+    COMMANDS.with(|commands| commands.borrow_mut().set_scope(Some(viewport), z));
+    let ret = f();
+    COMMANDS.with(|commands| commands.borrow_mut().set_scope(None, 0));
+    FRAME.with(|frame| frame.borrow_mut().set_scope(None));
+    ret
+}
+
+// Size of the caller's pixel space: its viewport, or else the whole screen
+fn view_size() -> (f32, f32) {
+    match FRAME.with(|frame| frame.borrow().viewport) {
+        Some(viewport) => (viewport.w as f32, viewport.h as f32),
+        None => {
+            let (w, h) = screen_size();
+            (w as f32, h as f32)
+        },
+    }
+}
+
+// Saves the current frame as a PNG, once it's finished drawing
+pub fn capture_frame(path: PathBuf) {
+    FRAME.with(|frame| frame.borrow_mut().captures.push(path));
+}
+
+// Saves the current frame's command list as text, once it's finished recording
+pub fn dump_commands(path: PathBuf) {
+    FRAME.with(|frame| frame.borrow_mut().command_dumps.push(path));
+}
+
+// Takes a screenshot, at the next free numbered path
+pub fn capture_screenshot() {
+    let path = capture::screenshot_path();
+    println!("Saving screenshot: {}", path.display());
+    capture_frame(path);
+}
+
+pub fn import_module(component: &Rc<RefCell<Component>>) -> ImportModule {
+    let store = &component.borrow().store;
+    let mut ret = ImportModule::new();
+    // Original behavior: fills the middle quarter of the screen (or viewport)
+    ret.add_func("drawImage", Func::wrap(&store, |tex_id: i32| {
+        let (w, h) = view_size();
+        let transform = math::rect_transform(w / 4.0, h / 4.0, w / 2.0, h / 2.0, 1.0, 0.0);
+        draw_image(tex_id, &transform, FULL_SOURCE, NO_TINT);
+    }));
+    // Live, so they're right during init and after a resize mid-frame.
+    // Components given a viewport get its size.
+    ret.add_func("screenWidth", Func::wrap(&store, || view_size().0 as i32));
+    ret.add_func("screenHeight", Func::wrap(&store, || view_size().1 as i32));
+    // Draws subsequent images over a transparency checkerboard, until the end of the
+    // frame, or of this call for components with a viewport
+    ret.add_func("setCheckerboard", Func::wrap(&store, |enabled: i32| {
+        FRAME.with(|frame| frame.borrow_mut().checkerboard_enabled = enabled != 0);
+    }));
+    ret.add_func("drawImageRect", Func::wrap(&store, |tex_id: i32, x: f32, y: f32, w: f32, h: f32| {
+        let transform = math::rect_transform(x, y, w, h, 1.0, 0.0);
+        draw_image(tex_id, &transform, FULL_SOURCE, NO_TINT);
+    }));
+    // Source rect is in UV space, (0, 0, 1, 1) being the whole image
+    ret.add_func("drawImageSub", Func::wrap(&store,
+        |tex_id: i32, x: f32, y: f32, w: f32, h: f32, u: f32, v: f32, uw: f32, vh: f32| {
+            let transform = math::rect_transform(x, y, w, h, 1.0, 0.0);
+            draw_image(tex_id, &transform, [u, v, uw, vh], NO_TINT);
+        }));
+    ret.add_func("drawImageEx", Func::wrap(&store,
+        |tex_id: i32, x: f32, y: f32, w: f32, h: f32, u: f32, v: f32, uw: f32, vh: f32,
+         scale: f32, rotation: f32, tint: i32| {
+            let transform = math::rect_transform(x, y, w, h, scale, rotation);
+            draw_image(tex_id, &transform, [u, v, uw, vh], math::unpack_color(tint));
+        }));
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("allocImage", Func::wrap(&store, move || {
+            let tex_id = with_renderer(|r| r.alloc_image());
+            component_weak.upgrade().unwrap().borrow_mut().images.push(tex_id);
+            tex_id as i32
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("freeImage", Func::wrap(&store, move |tex_id: i32| -> Result<(), Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let mut component_ref = component_rc.borrow_mut();
+            let idx = component_ref.images.iter().position(|&id| id == tex_id as u32)
+                .ok_or_else(|| Trap::new(format!("freeImage: image {} not owned by caller", tex_id)))?;
+            component_ref.images.swap_remove(idx);
+            free_images(&[tex_id as u32]);
+            Ok(())
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("updateImage", Func::wrap(&store, move |tex_id: i32, image_ptr: i32, tex_w: i32, tex_h: i32| -> Result<(), Trap> {
+            check_image("updateImage", &component_weak, tex_id as u32)?;
+            if COMMANDS.with(|commands| commands.borrow().open_targets().contains(&(tex_id as u32))) {
+                return Err(Trap::new(format!("updateImage: image {} is being drawn into", tex_id)));
+            }
+            if tex_w > MAX_IMAGE_SIZE || tex_h > MAX_IMAGE_SIZE {
+                return Err(Trap::new(format!("updateImage: invalid size {}x{}", tex_w, tex_h)));
+            }
+            let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+            let pixels = guest_image(&memory, image_ptr, tex_w, tex_h)?;
+            // A full upload supersedes anything still pending
+            PENDING_UPLOADS.with(|pending| pending.borrow_mut().remove(&(tex_id as u32)));
+            with_renderer(|r| r.upload_image(tex_id as u32, pixels, tex_w, tex_h));
+            Ok(())
+        }));
+    }
+    // Uploads only the (x, y, rw, rh) part of a w*h image. The image needs to have
+    // had a full updateImage first, which sets its size.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("updateImageRegion", Func::wrap(&store,
+            move |tex_id: i32, image_ptr: i32, tex_w: i32, tex_h: i32, x: i32, y: i32, rw: i32, rh: i32| -> Result<(), Trap> {
+                check_image("updateImageRegion", &component_weak, tex_id as u32)?;
+                if tex_w > MAX_IMAGE_SIZE || tex_h > MAX_IMAGE_SIZE {
+                    return Err(Trap::new(format!("updateImageRegion: invalid size {}x{}", tex_w, tex_h)));
+                }
+                let (x1, y1) = check_region(tex_w, tex_h, x, y, rw, rh)?;
+                let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+                let pixels = guest_image(&memory, image_ptr, tex_w, tex_h)?;
+                upload_region(tex_id as u32, pixels, tex_w, x, y, x1, y1);
+                Ok(())
+            }));
+    }
+    // Batched version of updateImageRegion: dirty regions are merged, and committed
+    // once per frame, or before the image is next drawn
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("markImageDirty", Func::wrap(&store,
+            move |tex_id: i32, image_ptr: i32, tex_w: i32, tex_h: i32, x: i32, y: i32, rw: i32, rh: i32| -> Result<(), Trap> {
+                let tex_id = tex_id as u32;
+                check_image("markImageDirty", &component_weak, tex_id)?;
+                let (x1, y1) = check_region(tex_w, tex_h, x, y, rw, rh)?;
+                PENDING_UPLOADS.with(|pending| {
+                    let mut pending = pending.borrow_mut();
+                    let mergeable = pending.get(&tex_id).map_or(false, |upload| {
+                        upload.image_ptr == image_ptr && upload.tex_w == tex_w && upload.tex_h == tex_h
+                    });
+                    if mergeable {
+                        let upload = pending.get_mut(&tex_id).unwrap();
+                        upload.x0 = upload.x0.min(x);
+                        upload.y0 = upload.y0.min(y);
+                        upload.x1 = upload.x1.max(x1);
+                        upload.y1 = upload.y1.max(y1);
+                    } else {
+                        // New, or the image moved or changed size so the old region means nothing
+                        pending.insert(tex_id, PendingUpload {
+                            component: component_weak.clone(),
+                            image_ptr, tex_w, tex_h,
+                            x0: x, y0: y, x1, y1,
+                        });
+                    }
+                });
+                Ok(())
+            }));
+    }
+    // 0 for nearest, 1 for linear, 2 for mipmapped. Sticks through later uploads.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setImageSampling", Func::wrap(&store, move |tex_id: i32, mode: i32| -> Result<(), Trap> {
+            let tex_id = tex_id as u32;
+            let sampling = Sampling::from_i32(mode)
+                .ok_or_else(|| Trap::new(format!("setImageSampling: unknown mode {}", mode)))?;
+            if AtlasPages::is_entry(tex_id) {
+                return Err(Trap::new("setImageSampling: atlas images use their page's sampling"));
+            }
+            if !component_weak.upgrade().unwrap().borrow().images.contains(&tex_id) {
+                return Err(Trap::new(format!("setImageSampling: image {} not owned by caller", tex_id)));
+            }
+            with_renderer(|r| r.set_sampling(tex_id, sampling));
+            Ok(())
+        }));
+    }
+    // Atlas images are small images packed into shared pages, which draw in one batch.
+    // Their ids work with every draw import. atlasAdd copies a w*h image out of guest
+    // memory, returning 0 if it's too big for a page.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("atlasAdd", Func::wrap(&store, move |image_ptr: i32, w: i32, h: i32| -> Result<i32, Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let memory = component_rc.borrow().memory()?;
+            let pixels = guest_image(&memory, image_ptr, w, h)?;
+            let added = ATLAS.with(|atlas| {
+                let mut atlas = atlas.borrow_mut();
+                let (id, _) = atlas.add(w, h, new_atlas_page)?;
+                write_atlas_entry(&mut atlas, id, pixels);
+                Some(id)
+            });
+            match added {
+                Some(id) => {
+                    component_rc.borrow_mut().atlas_images.push(id);
+                    Ok(id as i32)
+                },
+                None => {
+                    println!("atlasAdd: a {}x{} image doesn't fit on a {}px page", w, h, atlas::PAGE_SIZE);
+                    Ok(0)
+                },
+            }
+        }));
+    }
+    // Replaces an atlas image's pixels, which have to be the same size as before
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("atlasUpdate", Func::wrap(&store, move |id: i32, image_ptr: i32, w: i32, h: i32| -> Result<(), Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            if !component_rc.borrow().atlas_images.contains(&(id as u32)) {
+                return Err(Trap::new(format!("atlasUpdate: atlas image {} not owned by caller", id)));
+            }
+            let placement = ATLAS.with(|atlas| atlas.borrow().get(id as u32))
+                .ok_or_else(|| Trap::new(format!("atlasUpdate: no atlas image {}", id)))?;
+            if (placement.w, placement.h) != (w, h) {
+                return Err(Trap::new(format!("atlasUpdate: atlas image {} is {}x{}, not {}x{}",
+                    id, placement.w, placement.h, w, h)));
+            }
+            let memory = component_rc.borrow().memory()?;
+            let pixels = guest_image(&memory, image_ptr, w, h)?;
+            ATLAS.with(|atlas| write_atlas_entry(&mut atlas.borrow_mut(), id as u32, pixels));
+            Ok(())
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("atlasRemove", Func::wrap(&store, move |id: i32| -> Result<(), Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let mut component_ref = component_rc.borrow_mut();
+            let idx = component_ref.atlas_images.iter().position(|&owned| owned == id as u32)
+                .ok_or_else(|| Trap::new(format!("atlasRemove: atlas image {} not owned by caller", id)))?;
+            component_ref.atlas_images.swap_remove(idx);
+            free_atlas_images(&[id as u32]);
+            Ok(())
+        }));
+    }
+    // Render targets are images that draws can be redirected into, until endTarget.
+    // Inside one, pixel space covers the target rather than the screen. Targets are
+    // drawn before the screen, so drawing one shows the last thing drawn into it.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("allocTarget", Func::wrap(&store, move |w: i32, h: i32| -> Result<i32, Trap> {
+            if w <= 0 || h <= 0 || w > MAX_IMAGE_SIZE || h > MAX_IMAGE_SIZE {
+                return Err(Trap::new(format!("allocTarget: invalid size {}x{}", w, h)));
+            }
+            let tex_id = with_renderer(|r| r.alloc_target(w, h));
+            component_weak.upgrade().unwrap().borrow_mut().images.push(tex_id);
+            Ok(tex_id as i32)
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("beginTarget", Func::wrap(&store, move |tex_id: i32| -> Result<(), Trap> {
+            let tex_id = tex_id as u32;
+            if !component_weak.upgrade().unwrap().borrow().images.contains(&tex_id) {
+                return Err(Trap::new(format!("beginTarget: image {} not owned by caller", tex_id)));
+            }
+            if !with_renderer(|r| r.is_target(tex_id)) {
+                return Err(Trap::new(format!("beginTarget: image {} is not a render target", tex_id)));
+            }
+            COMMANDS.with(|commands| commands.borrow_mut().begin_target(tex_id));
+            Ok(())
+        }));
+    }
+    ret.add_func("endTarget", Func::wrap(&store, || -> Result<(), Trap> {
+        COMMANDS.with(|commands| commands.borrow_mut().end_target())
+            .ok_or_else(|| Trap::new("endTarget called without a beginTarget"))?;
+        Ok(())
+    }));
+    // Fills whatever's being drawn to: the screen, or just the caller's viewport of it,
+    // or a render target
+    ret.add_func("clear", Func::wrap(&store, |color: i32| {
+        COMMANDS.with(|commands| commands.borrow_mut().clear(math::unpack_color(color)));
+    }));
+    // Draws after this one, in this frame, go on the given layer. Higher layers
+    // are drawn on top; within a layer, things are drawn in order.
+    ret.add_func("setLayer", Func::wrap(&store, |layer: i32| {
+        COMMANDS.with(|commands| commands.borrow_mut().set_layer(layer));
+    }));
+    // Shapes share pixel space with images, colors are 0xAABBGGRR
+    ret.add_func("fillRect", Func::wrap(&store, |x: f32, y: f32, w: f32, h: f32, color: i32| {
+        record_shapes(|batch| batch.fill_rect(x, y, w, h, math::unpack_color(color)));
+    }));
+    ret.add_func("strokeRect", Func::wrap(&store, |x: f32, y: f32, w: f32, h: f32, width: f32, color: i32| {
+        record_shapes(|batch| batch.stroke_rect(x, y, w, h, width, math::unpack_color(color)));
+    }));
+    ret.add_func("drawLine", Func::wrap(&store, |x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: i32| {
+        record_shapes(|batch| batch.line(x0, y0, x1, y1, width, math::unpack_color(color)));
+    }));
+    ret.add_func("fillCircle", Func::wrap(&store, |x: f32, y: f32, radius: f32, color: i32| {
+        record_shapes(|batch| batch.fill_circle(x, y, radius, math::unpack_color(color)));
+    }));
+    ret.add_func("strokeCircle", Func::wrap(&store, |x: f32, y: f32, radius: f32, width: f32, color: i32| {
+        record_shapes(|batch| batch.stroke_circle(x, y, radius, width, math::unpack_color(color)));
+    }));
+    // `points_ptr` is `count` (x, y) pairs of f32s. Closed polylines join the last point to the first.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("drawPolyline", Func::wrap(&store,
+            move |points_ptr: i32, count: i32, width: f32, closed: i32, color: i32| -> Result<(), Trap> {
+                let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+                let points = read_points(&memory, points_ptr, count)?;
+                record_shapes(|batch| batch.polyline(&points, width, closed != 0, math::unpack_color(color)));
+                Ok(())
+            }));
+    }
+    // Text is positioned by its top-left corner, size is the line height in pixels
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("drawText", Func::wrap(&store, move |text_ptr: i32, x: f32, y: f32, size: f32, color: i32| -> Result<(), Trap> {
+            let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+            let text = read_string(&memory, text_ptr)?;
+            let tint = math::unpack_color(color);
+            with_font_atlas(size, |atlas, tex_id| {
+                for quad in atlas.layout(&text, x, y, size) {
+                    let [x, y, w, h] = quad.dest;
+                    let transform = math::rect_transform(x, y, w, h, 1.0, 0.0);
+                    draw_quad(tex_id, &transform, quad.source, tint, false, None);
+                }
+            });
+            Ok(())
+        }));
+    }
+    // Writes the text's pixel width and height, as two s32s, to `out_ptr`
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("measureText", Func::wrap(&store, move |text_ptr: i32, size: f32, out_ptr: i32| -> Result<(), Trap> {
+            let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+            let text = read_string(&memory, text_ptr)?;
+            let (w, h) = with_font_atlas(size, |atlas, _| atlas.measure(&text, size));
+            let data = unsafe { memory.data_unchecked_mut() };
+            let out = data.get_mut(out_ptr as u32 as usize..out_ptr as u32 as usize + 8)
+                .ok_or_else(|| Trap::new("measureText: out_ptr is outside guest memory"))?;
+            out[0..4].copy_from_slice(&(w.ceil() as i32).to_le_bytes());
+            out[4..8].copy_from_slice(&(h.ceil() as i32).to_le_bytes());
+            Ok(())
+        }));
+    }
+    // Decodes a PNG, JPEG, WebP or BMP file into a new image, and writes its width and
+    // height as two s32s to `out_ptr`. On failure, returns -1 if the file can't be read,
+    // -2 if it's not a supported format, or -3 if it doesn't decode.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("loadImage", Func::wrap(&store, move |path_ptr: i32, out_ptr: i32| -> Result<i32, Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let memory = component_rc.borrow().memory()?;
+            let path = read_string(&memory, path_ptr)?;
+            let data = unsafe { memory.data_unchecked_mut() };
+            let out = data.get_mut(out_ptr as u32 as usize..out_ptr as u32 as usize + 8)
+                .ok_or_else(|| Trap::new("loadImage: out_ptr is outside guest memory"))?;
+            let image = match decode::load(&path) {
+                Ok(image) => image,
+                Err(e) => {
+                    println!("Failed to load image {}: {}", path, e);
+                    return Ok(e.code());
+                },
+            };
+            out[0..4].copy_from_slice(&image.w.to_le_bytes());
+            out[4..8].copy_from_slice(&image.h.to_le_bytes());
+            let tex_id = with_renderer(|r| {
+                let tex_id = r.alloc_image();
+                r.upload_image(tex_id, &image.pixels, image.w, image.h);
+                tex_id
+            });
+            component_rc.borrow_mut().images.push(tex_id);
+            Ok(tex_id as i32)
+        }));
+    }
+    // Copies a w*h image back into guest memory at `image_ptr`, e.g. one from loadImage.
+    // Any image can be read, not just the caller's own. Draws into targets only land
+    // at the end of the frame, so a target reads back as of the last one.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("readImage", Func::wrap(&store, move |tex_id: i32, image_ptr: i32, tex_w: i32, tex_h: i32| -> Result<(), Trap> {
+            commit_upload(tex_id as u32);
+            let (w, h, pixels) = with_renderer(|r| r.read_image(tex_id as u32))
+                .ok_or_else(|| Trap::new(format!("readImage: no image {}", tex_id)))?;
+            if (w, h) != (tex_w, tex_h) {
+                return Err(Trap::new(format!("readImage: image {} is {}x{}, not {}x{}", tex_id, w, h, tex_w, tex_h)));
+            }
+            let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+            let data = unsafe { memory.data_unchecked_mut() };
+            let start = image_ptr as u32 as usize;
+            data.get_mut(start..start + pixels.len())
+                .ok_or_else(|| Trap::new(format!("Image at {} ({}x{}) is outside guest memory", image_ptr, tex_w, tex_h)))?
+                .copy_from_slice(&pixels);
+            Ok(())
+        }));
+    }
+    // Custom shaders are GLSL 330 fragment shaders defining `vec4 effect(vec2 uv)`,
+    // which returns the straight-alpha color at uv (see effect_prelude.frag).
+    // Returns 0 if the shader doesn't build, after logging why.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("createShader", Func::wrap(&store, move |source_ptr: i32| -> Result<i32, Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let memory = component_rc.borrow().memory()?;
+            let source = read_string(&memory, source_ptr)?;
+            match with_renderer(|r| r.create_shader(&source)) {
+                Ok(shader_id) => {
+                    SHADERS.with(|shaders| shaders.borrow_mut().insert(shader_id, Rc::new(Vec::new())));
+                    component_rc.borrow_mut().shaders.push(shader_id);
+                    Ok(shader_id as i32)
+                },
+                Err(e) => {
+                    println!("Failed to create shader: {}", e);
+                    Ok(0)
+                },
+            }
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("freeShader", Func::wrap(&store, move |shader_id: i32| -> Result<(), Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let mut component_ref = component_rc.borrow_mut();
+            let idx = component_ref.shaders.iter().position(|&id| id == shader_id as u32)
+                .ok_or_else(|| Trap::new(format!("freeShader: shader {} not owned by caller", shader_id)))?;
+            component_ref.shaders.swap_remove(idx);
+            free_shaders(&[shader_id as u32]);
+            Ok(())
+        }));
+    }
+    // Draws subsequent images through a custom shader, until the end of the frame, or of
+    // this call for components with a viewport. 0 goes back to the default. Text and
+    // shapes aren't affected.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShader", Func::wrap(&store, move |shader_id: i32| -> Result<(), Trap> {
+            let shader = match shader_id {
+                0 => None,
+                _ => Some(check_shader("setShader", &component_weak, shader_id)?),
+            };
+            FRAME.with(|frame| frame.borrow_mut().shader = shader);
+            Ok(())
+        }));
+    }
+    // Uniforms are set by name, and keep their values until set again. Draws already
+    // recorded keep the values they were recorded with.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShaderFloat", Func::wrap(&store, move |shader_id: i32, name_ptr: i32, x: f32| -> Result<(), Trap> {
+            set_uniform("setShaderFloat", &component_weak, shader_id, name_ptr, Uniform::Float(x))
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShaderVec2", Func::wrap(&store, move |shader_id: i32, name_ptr: i32, x: f32, y: f32| -> Result<(), Trap> {
+            set_uniform("setShaderVec2", &component_weak, shader_id, name_ptr, Uniform::Vec2([x, y]))
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShaderVec3", Func::wrap(&store, move |shader_id: i32, name_ptr: i32, x: f32, y: f32, z: f32| -> Result<(), Trap> {
+            set_uniform("setShaderVec3", &component_weak, shader_id, name_ptr, Uniform::Vec3([x, y, z]))
+        }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShaderVec4", Func::wrap(&store,
+            move |shader_id: i32, name_ptr: i32, x: f32, y: f32, z: f32, w: f32| -> Result<(), Trap> {
+                set_uniform("setShaderVec4", &component_weak, shader_id, name_ptr, Uniform::Vec4([x, y, z, w]))
+            }));
+    }
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("setShaderInt", Func::wrap(&store, move |shader_id: i32, name_ptr: i32, x: i32| -> Result<(), Trap> {
+            set_uniform("setShaderInt", &component_weak, shader_id, name_ptr, Uniform::Int(x))
+        }));
+    }
+    // Runs a custom shader over the screen once everything's drawn, sampling what's
+    // there. Components with a viewport only affect their viewport. Post passes run
+    // in the order they're added.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("addPostPass", Func::wrap(&store, move |shader_id: i32| -> Result<(), Trap> {
+            let shader = shader_use(check_shader("addPostPass", &component_weak, shader_id)?);
+            COMMANDS.with(|commands| commands.borrow_mut().post_pass(shader));
+            Ok(())
+        }));
+    }
+    // Same as the screenshot hotkey
+    ret.add_func("captureFrame", Func::wrap(&store, || {
+        capture_screenshot();
+    }));
+    ret
+}
+
+// Uploads every region marked dirty since the last commit
+pub fn commit_uploads() {
+    let pending = PENDING_UPLOADS.with(|pending| std::mem::replace(&mut *pending.borrow_mut(), HashMap::new()));
+    for (tex_id, upload) in pending {
+        upload.commit(tex_id);
+    }
+}
+
+pub fn free_images(tex_ids: &[u32]) {
+    PENDING_UPLOADS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        for tex_id in tex_ids {
+            pending.remove(tex_id);
+        }
+    });
+    // Freeing a target that's being drawn into sends draws to the next one out
+    COMMANDS.with(|commands| {
+        let mut commands = commands.borrow_mut();
+        for &tex_id in tex_ids {
+            commands.forget_target(tex_id);
+        }
+    });
+    FRAME.with(|frame| frame.borrow_mut().freed.extend_from_slice(tex_ids));
+}
+
+// Their space on the page is reused by later atlas images. Draws already recorded
+// this frame can show whatever replaces them. Pages left empty are freed.
+pub fn free_atlas_images(ids: &[u32]) {
+    let empty_pages = ATLAS.with(|atlas| {
+        let mut atlas = atlas.borrow_mut();
+        for &id in ids {
+            atlas.remove(id);
+        }
+        atlas.take_empty_pages()
+    });
+    free_images(&empty_pages);
+}
+
+pub fn free_shaders(shader_ids: &[u32]) {
+    SHADERS.with(|shaders| {
+        let mut shaders = shaders.borrow_mut();
+        for shader_id in shader_ids {
+            shaders.remove(shader_id);
+        }
+    });
+    FRAME.with(|frame| {
+        let mut frame = frame.borrow_mut();
+        if frame.shader.map_or(false, |id| shader_ids.contains(&id)) {
+            frame.shader = None;
+        }
+        // Draws recorded with them still need them this frame
+        frame.freed_shaders.extend_from_slice(shader_ids);
+    });
+}
+
+impl PendingUpload {
+    fn commit(&self, tex_id: u32) {
+        // The owner may have been dropped since marking; then there's nothing to show anyway
+        let component_rc = match self.component.upgrade() {
+            Some(rc) => rc,
+            None => return,
+        };
+        let memory = match component_rc.borrow().memory() {
+            Ok(memory) => memory,
+            Err(e) => {
+                println!("Dropping upload for image {}: {}", tex_id, e);
+                return;
+            }
+        };
+        match guest_image(&memory, self.image_ptr, self.tex_w, self.tex_h) {
+            Ok(pixels) => upload_region(tex_id, pixels, self.tex_w, self.x0, self.y0, self.x1, self.y1),
+            Err(e) => println!("Dropping upload for image {}: {}", tex_id, e),
+        }
+    }
+}
+
+// Runs `f` with the atlas for text of the given size, building it if needed
+fn with_font_atlas<T, F: FnOnce(&Atlas, u32) -> T>(size: f32, f: F) -> T {
+    FONTS.with(|fonts| {
+        let mut fonts = fonts.borrow_mut();
+        let fonts = &mut *fonts;
+        let mut key = 0;
+        if let Some(ttf) = &fonts.ttf {
+            // NaN comes out as MIN_FONT_SIZE
+            key = size.round().max(MIN_FONT_SIZE).min(MAX_FONT_SIZE) as u32;
+            if fonts.failed.contains(&key) {
+                key = 0;
+            } else if !fonts.atlases.contains_key(&key) {
+                match Atlas::rasterize(ttf, text::FONT_PATH, key as u16) {
+                    Ok(atlas) => {
+                        let tex_id = upload_atlas(&atlas);
+                        fonts.atlases.insert(key, (atlas, tex_id));
+                    },
+                    Err(e) => {
+                        println!("Failed to rasterize {} at {}px, using bitmap font: {}", text::FONT_PATH, key, e);
+                        fonts.failed.insert(key);
+                        key = 0;
+                    },
+                }
+            }
+        }
+        if key != 0 {
+            fonts.recent.retain(|&recent| recent != key);
+            fonts.recent.push(key);
+            if fonts.recent.len() > MAX_FONT_ATLASES {
+                let evicted = fonts.recent.remove(0);
+                // Draws already recorded with it are done with it by the end of the frame
+                let (_, tex_id) = fonts.atlases.remove(&evicted).unwrap();
+                free_images(&[tex_id]);
+            }
+        }
+        if !fonts.atlases.contains_key(&key) {
+            let atlas = Atlas::bitmap();
+            let tex_id = upload_atlas(&atlas);
+            fonts.atlases.insert(key, (atlas, tex_id));
+        }
+        let (atlas, tex_id) = &fonts.atlases[&key];
+        f(atlas, *tex_id)
+    })
+}
+
+// A blank atlas page, for AtlasPages::add
+fn new_atlas_page() -> u32 {
+    let size = atlas::PAGE_SIZE;
+    with_renderer(|r| {
+        let tex_id = r.alloc_image();
+        r.upload_image(tex_id, &vec![0; (size * size * 4) as usize], size, size);
+        tex_id
+    })
+}
+
+fn write_atlas_entry(atlas: &mut AtlasPages, id: u32, pixels: &[u8]) {
+    if let Some((page_pixels, placement)) = atlas.write(id, pixels) {
+        let (x, y) = (placement.x, placement.y);
+        upload_region(placement.page, page_pixels, atlas::PAGE_SIZE, x, y, x + placement.w, y + placement.h);
+    }
+}
+
+fn upload_atlas(atlas: &Atlas) -> u32 {
+    with_renderer(|r| {
+        let tex_id = r.alloc_image();
+        r.upload_image(tex_id, &atlas.pixels, atlas.width, atlas.height);
+        if atlas.smooth {
+            r.set_sampling(tex_id, Sampling::Linear);
+        }
+        tex_id
+    })
+}
+
+// Reads `count` (x, y) f32 pairs out of guest memory
+fn read_points(memory: &Memory, ptr: i32, count: i32) -> Result<Vec<(f32, f32)>, Trap> {
+    let data = unsafe { memory.data_unchecked() };
+    let start = ptr as u32 as usize;
+    let end = start + count.max(0) as usize * 8;
+    let bytes = data.get(start..end)
+        .ok_or_else(|| Trap::new(format!("{} points at {} are outside guest memory", count, ptr)))?;
+    let read_f32 = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    Ok(bytes.chunks_exact(8).map(|p| (read_f32(&p[0..4]), read_f32(&p[4..8]))).collect())
+}
+
+// Reads a NUL-terminated UTF-8 string out of guest memory
+pub fn read_string(memory: &Memory, ptr: i32) -> Result<String, Trap> {
+    let data = unsafe { memory.data_unchecked() };
+    let start = ptr as u32 as usize;
+    let len = data.get(start..).and_then(|rest| rest.iter().position(|&b| b == 0))
+        .ok_or_else(|| Trap::new(format!("Unterminated string at {}", ptr)))?;
+    Ok(String::from_utf8_lossy(&data[start..start + len]).into_owned())
+}
+
+// Commits a pending upload for one image, ahead of the rest of the frame's
+fn commit_upload(tex_id: u32) {
+    let upload = PENDING_UPLOADS.with(|pending| pending.borrow_mut().remove(&tex_id));
+    if let Some(upload) = upload {
+        upload.commit(tex_id);
+    }
+}
+
+// Borrows a w*h image straight out of guest memory, no copy
+fn guest_image(memory: &Memory, image_ptr: i32, tex_w: i32, tex_h: i32) -> Result<&[u8], Trap> {
+    let data = unsafe { memory.data_unchecked() };
+    let start = image_ptr as u32 as usize;
+    (tex_w.max(0) as usize).checked_mul(tex_h.max(0) as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .and_then(|len| start.checked_add(len))
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| Trap::new(format!("Image at {} ({}x{}) is outside guest memory", image_ptr, tex_w, tex_h)))
+}
+
+// Returns the region's far corner, which can't overflow once it's inside the image
+fn check_region(tex_w: i32, tex_h: i32, x: i32, y: i32, rw: i32, rh: i32) -> Result<(i32, i32), Trap> {
+    let x1 = x.checked_add(rw).filter(|&x1| x1 <= tex_w);
+    let y1 = y.checked_add(rh).filter(|&y1| y1 <= tex_h);
+    match (x1, y1) {
+        (Some(x1), Some(y1)) if x >= 0 && y >= 0 && rw >= 0 && rh >= 0 => Ok((x1, y1)),
+        _ => Err(Trap::new(format!("Region ({}, {}, {}, {}) is outside the {}x{} image", x, y, rw, rh, tex_w, tex_h))),
+    }
+}
+
+fn upload_region(tex_id: u32, pixels: &[u8], tex_w: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
+    if x1 <= x0 || y1 <= y0 {
+        return;
+    }
+    with_renderer(|r| r.upload_region(tex_id, pixels, tex_w, x0, y0, x1, y1));
+}
+
+// Back to straight alpha, for images stored premultiplied
+fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        if a != 0 {
+            for c in &mut pixel[0..3] {
+                *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+            }
+        }
+    }
+}
+
+// Traps unless the caller allocated `tex_id`, and hasn't freed it
+fn check_image(import: &str, component_weak: &Weak<RefCell<Component>>, tex_id: u32) -> Result<(), Trap> {
+    if !component_weak.upgrade().unwrap().borrow().images.contains(&tex_id) {
+        return Err(Trap::new(format!("{}: image {} not owned by caller", import, tex_id)));
+    }
+    Ok(())
+}
+
+// Traps unless `shader_id` is a live custom shader the caller created
+fn check_shader(import: &str, component_weak: &Weak<RefCell<Component>>, shader_id: i32) -> Result<u32, Trap> {
+    let shader_id = shader_id as u32;
+    if !SHADERS.with(|shaders| shaders.borrow().contains_key(&shader_id)) {
+        return Err(Trap::new(format!("{}: no shader {}", import, shader_id)));
+    }
+    if !component_weak.upgrade().unwrap().borrow().shaders.contains(&shader_id) {
+        return Err(Trap::new(format!("{}: shader {} not owned by caller", import, shader_id)));
+    }
+    Ok(shader_id)
+}
+
+// A custom shader, with a snapshot of its current uniforms
+fn shader_use(shader_id: u32) -> ShaderUse {
+    let uniforms = SHADERS.with(|shaders| shaders.borrow()[&shader_id].clone());
+    ShaderUse { id: shader_id, uniforms }
+}
+
+fn set_uniform(import: &str, component_weak: &Weak<RefCell<Component>>, shader_id: i32, name_ptr: i32, value: Uniform) -> Result<(), Trap> {
+    let shader_id = check_shader(import, component_weak, shader_id)?;
+    let memory = component_weak.upgrade().unwrap().borrow().memory()?;
+    let name = read_string(&memory, name_ptr)?;
+    SHADERS.with(|shaders| {
+        let mut shaders = shaders.borrow_mut();
+        // Copied if recorded draws still share the old values
+        let uniforms = Rc::make_mut(shaders.get_mut(&shader_id).unwrap());
+        match uniforms.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = value,
+            None => uniforms.push((name, value)),
+        }
+    });
+    Ok(())
+}
+
+// Draws an image, over the checkerboard and through the custom shader if those are set.
+// Atlas images draw the part of their page they're on.
+fn draw_image(tex_id: i32, transform: &Mat4, source_rect: [f32; 4], tint: [f32; 4]) {
+    let (tex_id, source_rect) = if AtlasPages::is_entry(tex_id as u32) {
+        match ATLAS.with(|atlas| atlas.borrow().get(tex_id as u32)) {
+            Some(placement) => (placement.page as i32, placement.source_rect(source_rect)),
+            None => return,
+        }
+    } else {
+        (tex_id, source_rect)
+    };
+    let (checkerboard, shader) = FRAME.with(|frame| {
+        let frame = frame.borrow();
+        (frame.checkerboard_enabled, frame.shader)
+    });
+    draw_quad(tex_id as u32, transform, source_rect, tint, checkerboard, shader.map(shader_use));
+}
+
+fn draw_quad(tex_id: u32, transform: &Mat4, source_rect: [f32; 4], tint: [f32; 4], checkerboard: bool, shader: Option<ShaderUse>) {
+    let quad = Quad { transform: *transform, source_rect, tint };
+    COMMANDS.with(|commands| commands.borrow_mut().quad(tex_id, quad, checkerboard, shader));
+}
+
+// Records the shapes `f` adds as one draw
+fn record_shapes<F: FnOnce(&mut ShapeBatch)>(f: F) {
+    let mut batch = ShapeBatch::default();
+    f(&mut batch);
+    COMMANDS.with(|commands| commands.borrow_mut().triangles(batch.vertices));
+}
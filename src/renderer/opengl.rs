@@ -0,0 +1,620 @@
+// OpenGL 3.3 backend, drawing to an SDL window
+
+extern crate sdl2;
+extern crate gl;
+
+use gl::types::*;
+use sdl2::video::{GLContext, GLProfile, Window};
+use std::collections::HashMap;
+
+use crate::math;
+use crate::shapes::Vertex;
+use super::{Quad, Renderer, Sampling, ShaderUse, Uniform, Uniforms, Viewport};
+use super::shader::{self, ProgramSource, ShaderProgram};
+
+pub struct GlRenderer {
+    window: Window,
+    textured: TexturedProgram,
+    textured_source: ProgramSource,
+    vao: GLuint,
+
+    shapes: ShapeProgram,
+    shapes_source: ProgramSource,
+    // Shape vertices get streamed into shape_vbo on every draw
+    shape_vao: GLuint,
+    shape_vbo: GLuint,
+
+    // Components' custom shaders, by id. They take the projection at draw time,
+    // so the current one is kept here.
+    custom: HashMap<u32, TexturedProgram>,
+    next_shader: u32,
+    projection: math::Mat4,
+    // Copy of the screen for post passes to sample, and its size
+    post_texture: GLuint,
+    post_size: (i32, i32),
+
+    // Framebuffers of render targets, by texture id
+    targets: HashMap<u32, Target>,
+    // Images that aren't Nearest, by texture id
+    sampling: HashMap<u32, Sampling>,
+    current_target: Option<u32>,
+
+    // Need to capture this so that it doesn't get Drop'd
+    #[allow(dead_code)]
+    gl_context: GLContext,
+}
+
+struct Target {
+    fbo: GLuint,
+    w: i32,
+    h: i32,
+}
+
+// Draws images
+struct TexturedProgram {
+    program: ShaderProgram,
+    // Uniform locations
+    projection: GLint,
+    transform: GLint,
+    source_rect: GLint,
+    tint: GLint,
+    checkerboard: GLint,
+    premultiplied: GLint,
+}
+impl TexturedProgram {
+    fn new(program: ShaderProgram) -> TexturedProgram {
+        program.set_used();
+        unsafe {
+            // Images are always drawn from texture unit 0
+            gl::Uniform1i(program.uniform_location("Texture"), 0);
+        }
+        TexturedProgram {
+            projection: program.uniform_location("Projection"),
+            transform: program.uniform_location("Transform"),
+            source_rect: program.uniform_location("SourceRect"),
+            tint: program.uniform_location("Tint"),
+            checkerboard: program.uniform_location("Checkerboard"),
+            premultiplied: program.uniform_location("Premultiplied"),
+            program,
+        }
+    }
+}
+
+// Draws shapes' colored triangles
+struct ShapeProgram {
+    program: ShaderProgram,
+    projection: GLint,
+}
+impl ShapeProgram {
+    fn new(program: ShaderProgram) -> ShapeProgram {
+        ShapeProgram { projection: program.uniform_location("Projection"), program }
+    }
+}
+
+impl GlRenderer {
+    pub fn new(sdl_context: &sdl2::Sdl) -> GlRenderer {
+        let video_subsystem = sdl_context.video().unwrap();
+
+        let gl_attr = video_subsystem.gl_attr();
+        gl_attr.set_context_profile(GLProfile::Core);
+        gl_attr.set_context_version(3, 3);
+
+        let window = video_subsystem.window("EdEditor", 800, 600)
+            .position_centered()
+            .opengl()
+            .resizable()
+            .allow_highdpi()
+            .build().unwrap();
+
+        println!("Initializing GL...");
+        let gl_context = window.gl_create_context().unwrap();
+        let _gl = gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
+        debug_assert_eq!(gl_attr.context_profile(), GLProfile::Core);
+        debug_assert_eq!(gl_attr.context_version(), (3, 3));
+
+        // Square model, 1x1, centered at 0
+        let vertices: Vec<f32> = vec![
+            // Position           UV
+            -0.5, -0.5, 0.0,      0.0, 0.0,
+            0.5, -0.5, 0.0,       1.0, 0.0,
+            0.5, 0.5, 0.0,        1.0, 1.0,
+
+            0.5, 0.5, 0.0,        1.0, 1.0,
+            -0.5, 0.5, 0.0,       0.0, 1.0,
+            -0.5, -0.5, 0.0,      0.0, 0.0,
+        ];
+        let mut vbo: GLuint = 0; // VBO to store vertex data
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::BufferData(
+                gl::ARRAY_BUFFER, // target
+                (vertices.len() * std::mem::size_of::<f32>()) as GLsizeiptr, // size of data in bytes
+                vertices.as_ptr() as *const GLvoid, // pointer to data
+                gl::STATIC_DRAW, // usage
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0); // unbind the buffer
+        }
+        let mut vao: GLuint = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            // Bind Position
+            gl::EnableVertexAttribArray(0); // this is "layout (location = 0)" in vertex shader
+            gl::VertexAttribPointer(
+                0, // index of the generic vertex attribute ("layout (location = 0)")
+                3, // the number of components per generic vertex attribute
+                gl::FLOAT, // data type
+                gl::FALSE, // normalized (int-to-float conversion)
+                (5 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                std::ptr::null() // offset of the first component
+            );
+
+            // Bind UV Coords
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1, // index of the generic vertex attribute ("layout (location = 0)")
+                2, // the number of components per generic vertex attribute
+                gl::FLOAT, // data type
+                gl::FALSE, // normalized (int-to-float conversion)
+                (5 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                (3 * std::mem::size_of::<f32>()) as *const GLvoid // offset of the first component
+            );
+
+            // unbind
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+
+        println!("Loading shaders");
+        // Nothing to fall back on yet, so these have to build
+        let mut textured_source = ProgramSource::new("textured.vert", "textured.frag");
+        let textured = TexturedProgram::new(textured_source.build().unwrap_or_else(|e| panic!("{}", e)));
+        let mut shapes_source = ProgramSource::new("simple.vert", "simple.frag");
+        let shapes = ShapeProgram::new(shapes_source.build().unwrap_or_else(|e| panic!("{}", e)));
+        let mut shape_vbo: GLuint = 0;
+        let shape_vao = shape_vao(&mut shape_vbo);
+
+        let mut post_texture: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut post_texture);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        GlRenderer {
+            window,
+            textured,
+            textured_source,
+            vao,
+            shapes,
+            shapes_source,
+            shape_vao,
+            shape_vbo,
+            custom: HashMap::new(),
+            // 0 stays invalid, guests get it back when a shader fails to build
+            next_shader: 1,
+            projection: math::ortho(1.0, 1.0),
+            post_texture,
+            post_size: (0, 0),
+            targets: HashMap::new(),
+            sampling: HashMap::new(),
+            current_target: None,
+            gl_context,
+        }
+    }
+
+    fn set_projection(&mut self, w: f32, h: f32) {
+        let projection = math::ortho(w, h);
+        self.projection = projection;
+        unsafe {
+            self.shapes.program.set_used();
+            gl::UniformMatrix4fv(self.shapes.projection, 1, gl::FALSE, projection.as_ptr());
+            self.textured.program.set_used();
+            gl::UniformMatrix4fv(self.textured.projection, 1, gl::FALSE, projection.as_ptr());
+        }
+    }
+
+    // Rebuilds any program whose files have changed. One that fails to build is
+    // reported, and the old program stays in use.
+    fn reload_shaders(&mut self) {
+        if self.textured_source.changed() {
+            match self.textured_source.build() {
+                Ok(program) => {
+                    println!("Reloaded textured shaders");
+                    self.textured = TexturedProgram::new(program);
+                },
+                Err(e) => println!("Keeping the old textured shaders: {}", e),
+            }
+        }
+        if self.shapes_source.changed() {
+            match self.shapes_source.build() {
+                Ok(program) => {
+                    println!("Reloaded shape shaders");
+                    self.shapes = ShapeProgram::new(program);
+                },
+                Err(e) => println!("Keeping the old shape shaders: {}", e),
+            }
+        }
+    }
+
+    fn current_fbo(&self) -> GLuint {
+        self.current_target.and_then(|id| self.targets.get(&id)).map_or(0, |target| target.fbo)
+    }
+
+    // Sets the bound texture's filters to match its sampling, rebuilding mipmaps if it has them
+    fn apply_sampling(&self, id: u32) {
+        let (min, mag) = match self.sampling.get(&id).copied().unwrap_or(Sampling::Nearest) {
+            Sampling::Nearest => (gl::NEAREST, gl::NEAREST),
+            Sampling::Linear => (gl::LINEAR, gl::LINEAR),
+            Sampling::Mipmapped => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+        };
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag as i32);
+        }
+        self.update_mipmaps(id);
+    }
+
+    // Mip levels are copies, so they're redone whenever the image changes
+    fn update_mipmaps(&self, id: u32) {
+        if self.sampling.get(&id) == Some(&Sampling::Mipmapped) {
+            unsafe {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
+    }
+
+    fn delete_target(&mut self, id: u32) {
+        if let Some(target) = self.targets.remove(&id) {
+            if self.current_target == Some(id) {
+                self.set_target(None);
+            }
+            unsafe {
+                gl::DeleteFramebuffers(1, &target.fbo);
+            }
+        }
+    }
+}
+
+impl Renderer for GlRenderer {
+    fn size(&self) -> (u32, u32) {
+        self.window.size()
+    }
+
+    fn drawable_size(&self) -> (u32, u32) {
+        self.window.drawable_size()
+    }
+
+    fn begin_frame(&mut self, clear_color: [f32; 4]) {
+        self.reload_shaders();
+        // Picks up the window's current size too, it may have been resized since last
+        // frame. Also sets the projection, which new programs don't have yet.
+        self.set_target(None);
+        self.clear(clear_color);
+    }
+
+    fn end_frame(&mut self) {
+        self.window.gl_swap_window();
+    }
+
+    fn alloc_image(&mut self) -> u32 {
+        let mut tex_id: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut tex_id);
+        }
+        tex_id
+    }
+
+    fn free_image(&mut self, id: u32) {
+        self.delete_target(id);
+        self.sampling.remove(&id);
+        unsafe {
+            gl::DeleteTextures(1, &id);
+        }
+    }
+
+    fn upload_image(&mut self, id: u32, pixels: &[u8], w: i32, h: i32) {
+        self.delete_target(id);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, w, h, 0, gl::RGBA,
+                gl::UNSIGNED_BYTE, pixels.as_ptr() as *const GLvoid);
+        }
+        self.apply_sampling(id);
+        unsafe {
+            // unbind
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    fn upload_region(&mut self, id: u32, pixels: &[u8], tex_w: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::PixelStorei(gl::UNPACK_ROW_LENGTH, tex_w);
+            gl::PixelStorei(gl::UNPACK_SKIP_PIXELS, x0);
+            gl::PixelStorei(gl::UNPACK_SKIP_ROWS, y0);
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, x0, y0, x1 - x0, y1 - y0, gl::RGBA,
+                gl::UNSIGNED_BYTE, pixels.as_ptr() as *const GLvoid);
+            gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+            gl::PixelStorei(gl::UNPACK_SKIP_PIXELS, 0);
+            gl::PixelStorei(gl::UNPACK_SKIP_ROWS, 0);
+        }
+        self.update_mipmaps(id);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    fn set_sampling(&mut self, id: u32, sampling: Sampling) {
+        match sampling {
+            Sampling::Nearest => self.sampling.remove(&id),
+            _ => self.sampling.insert(id, sampling),
+        };
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, id);
+        }
+        self.apply_sampling(id);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    fn alloc_target(&mut self, w: i32, h: i32) -> u32 {
+        let id = self.alloc_image();
+        let mut fbo: GLuint = 0;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, w, h, 0, gl::RGBA,
+                gl::UNSIGNED_BYTE, std::ptr::null());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, id, 0);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                println!("Render target {} ({}x{}) is incomplete", id, w, h);
+            }
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.current_fbo());
+        }
+        self.targets.insert(id, Target { fbo, w, h });
+        id
+    }
+
+    fn set_target(&mut self, target: Option<u32>) -> bool {
+        let (fbo, (pixel_w, pixel_h), (w, h)) = match target {
+            Some(id) => match self.targets.get(&id) {
+                Some(t) => (t.fbo, (t.w, t.h), (t.w, t.h)),
+                None => return false,
+            },
+            None => {
+                let (w, h) = self.size();
+                let (pixel_w, pixel_h) = self.drawable_size();
+                (0, (pixel_w as i32, pixel_h as i32), (w as i32, h as i32))
+            },
+        };
+        // Done drawing into the old target, so its mipmaps can catch up
+        if let Some(old) = self.current_target.filter(|&old| Some(old) != target) {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, old);
+            }
+            self.update_mipmaps(old);
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+        }
+        self.current_target = target;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::Viewport(0, 0, pixel_w, pixel_h);
+        }
+        self.set_projection(w as f32, h as f32);
+        true
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        let [r, g, b, a] = color;
+        unsafe {
+            gl::ClearColor(r * a, g * a, b * a, a);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn set_clip(&mut self, clip: Option<Viewport>) {
+        let clip = match clip {
+            Some(clip) => clip,
+            None => {
+                unsafe { gl::Disable(gl::SCISSOR_TEST) };
+                return;
+            },
+        };
+        // Scissor rects are in framebuffer pixels, which outnumber pixel space on high-DPI screens
+        let ((w, h), (pixel_w, pixel_h)) = (self.size(), self.drawable_size());
+        let (sx, sy) = (pixel_w as f32 / w as f32, pixel_h as f32 / h as f32);
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor((clip.x as f32 * sx) as GLint, (clip.y as f32 * sy) as GLint,
+                (clip.w as f32 * sx) as GLsizei, (clip.h as f32 * sy) as GLsizei);
+        }
+    }
+
+    fn is_target(&self, id: u32) -> bool {
+        self.targets.contains_key(&id)
+    }
+
+    fn create_shader(&mut self, source: &str) -> Result<u32, String> {
+        let program = TexturedProgram::new(shader::build_custom(source)?);
+        let id = self.next_shader;
+        self.next_shader += 1;
+        self.custom.insert(id, program);
+        Ok(id)
+    }
+
+    fn free_shader(&mut self, id: u32) {
+        self.custom.remove(&id);
+    }
+
+    // The image is bound once, only the per-quad uniforms change in between
+    fn draw_quads(&mut self, id: u32, quads: &[Quad], checkerboard: bool, shader: Option<&ShaderUse>) {
+        let custom = shader.and_then(|shader| Some((self.custom.get(&shader.id)?, shader)));
+        let textured = custom.map_or(&self.textured, |(program, _)| program);
+        textured.program.set_used();
+        unsafe {
+            if let Some((program, shader)) = custom {
+                gl::UniformMatrix4fv(program.projection, 1, gl::FALSE, self.projection.as_ptr());
+                set_uniforms(&program.program, &shader.uniforms);
+            }
+            gl::BindVertexArray(self.vao);
+            gl::Uniform1i(textured.premultiplied, self.targets.contains_key(&id) as GLint);
+            gl::Uniform1i(textured.checkerboard, checkerboard as GLint);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            for quad in quads {
+                gl::UniformMatrix4fv(textured.transform, 1, gl::FALSE, quad.transform.as_ptr());
+                gl::Uniform4fv(textured.source_rect, 1, quad.source_rect.as_ptr());
+                gl::Uniform4fv(textured.tint, 1, quad.tint.as_ptr());
+                gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            }
+        }
+    }
+
+    fn draw_triangles(&mut self, vertices: &[Vertex]) {
+        self.shapes.program.set_used();
+        unsafe {
+            gl::BindVertexArray(self.shape_vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.shape_vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<Vertex>()) as GLsizeiptr,
+                vertices.as_ptr() as *const GLvoid,
+                gl::STREAM_DRAW,
+            );
+            gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as GLsizei);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // Copies the screen into post_texture, then draws it back over itself through the
+    // shader. Blending's off, the shader's output replaces what was there.
+    fn post_pass(&mut self, shader: &ShaderUse) {
+        if !self.custom.contains_key(&shader.id) {
+            return;
+        }
+        let (w, h) = self.size();
+        let (pixel_w, pixel_h) = self.drawable_size();
+        let pixel_size = (pixel_w as i32, pixel_h as i32);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.post_texture);
+            if self.post_size != pixel_size {
+                gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as i32, pixel_size.0, pixel_size.1, 0, gl::RGBA,
+                    gl::UNSIGNED_BYTE, std::ptr::null());
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+                self.post_size = pixel_size;
+            }
+            // Not limited by the scissor, the whole screen can be sampled
+            gl::CopyTexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, 0, 0, pixel_size.0, pixel_size.1);
+        }
+        let program = &self.custom[&shader.id];
+        let transform = math::rect_transform(0.0, 0.0, w as f32, h as f32, 1.0, 0.0);
+        program.program.set_used();
+        unsafe {
+            gl::UniformMatrix4fv(program.projection, 1, gl::FALSE, self.projection.as_ptr());
+            set_uniforms(&program.program, &shader.uniforms);
+            gl::UniformMatrix4fv(program.transform, 1, gl::FALSE, transform.as_ptr());
+            gl::Uniform4f(program.source_rect, 0.0, 0.0, 1.0, 1.0);
+            gl::Uniform4f(program.tint, 1.0, 1.0, 1.0, 1.0);
+            gl::Uniform1i(program.checkerboard, 0);
+            // The screen is drawn premultiplied, like a target
+            gl::Uniform1i(program.premultiplied, 1);
+            gl::BindVertexArray(self.vao);
+            gl::Disable(gl::BLEND);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            gl::Enable(gl::BLEND);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // Reads the back buffer, so this has to happen before the swap
+    fn read_pixels(&mut self) -> Vec<u8> {
+        let (w, h) = self.drawable_size();
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, w as GLint, h as GLint, gl::RGBA, gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut GLvoid);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.current_fbo());
+        }
+        pixels
+    }
+
+    fn read_image(&mut self, id: u32) -> Option<(i32, i32, Vec<u8>)> {
+        let (mut w, mut h) = (0, 0);
+        let mut pixels;
+        unsafe {
+            if gl::IsTexture(id) == gl::FALSE {
+                return None;
+            }
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut w);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut h);
+            pixels = vec![0u8; (w * h * 4) as usize];
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut GLvoid);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        // Targets are drawn into premultiplied
+        if self.targets.contains_key(&id) {
+            super::unpremultiply(&mut pixels);
+        }
+        Some((w, h, pixels))
+    }
+}
+
+// Sets a custom program's uniforms by name. Names it doesn't use are skipped, as GL does.
+unsafe fn set_uniforms(program: &ShaderProgram, uniforms: &Uniforms) {
+    for (name, value) in uniforms {
+        let location = program.uniform_location(name);
+        match value {
+            Uniform::Float(x) => gl::Uniform1f(location, *x),
+            Uniform::Vec2(v) => gl::Uniform2fv(location, 1, v.as_ptr()),
+            Uniform::Vec3(v) => gl::Uniform3fv(location, 1, v.as_ptr()),
+            Uniform::Vec4(v) => gl::Uniform4fv(location, 1, v.as_ptr()),
+            Uniform::Int(x) => gl::Uniform1i(location, *x),
+        }
+    }
+}
+
+// VAO for shape vertices, streamed into `vbo`
+fn shape_vao(vbo: &mut GLuint) -> GLuint {
+    let stride = std::mem::size_of::<Vertex>() as GLint;
+    let mut vao: GLuint = 0;
+    unsafe {
+        gl::GenBuffers(1, vbo);
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, *vbo);
+
+        // Position
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+        // Color
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride,
+            (2 * std::mem::size_of::<f32>()) as *const GLvoid);
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::BindVertexArray(0);
+    }
+    vao
+}
@@ -0,0 +1,160 @@
+// GL shader programs, loaded from resources/shaders at runtime so they can be
+// rebuilt when their files change
+
+use gl::types::*;
+use std::{
+    ffi::CString,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub const SHADER_DIR: &str = "resources/shaders";
+
+struct Shader {
+    id: GLuint,
+}
+impl Shader {
+    // Errors start with the file name, followed by the compiler's log
+    fn from_file(path: &Path, kind: GLuint) -> Result<Shader, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Shader::from_source(&source, kind, &path.display().to_string())
+    }
+
+    // `name` is what errors call the shader
+    fn from_source(source: &str, kind: GLuint, name: &str) -> Result<Shader, String> {
+        let source = CString::new(source).map_err(|_| format!("{}: contains a NUL byte", name))?;
+        let shader = Shader { id: unsafe { gl::CreateShader(kind) } };
+        unsafe {
+            gl::ShaderSource(shader.id, 1, &source.as_ptr(), std::ptr::null());
+            gl::CompileShader(shader.id);
+            let mut success: GLint = 1;
+            gl::GetShaderiv(shader.id, gl::COMPILE_STATUS, &mut success);
+            if success == 0 {
+                let log = info_log(shader.id, gl::GetShaderiv, gl::GetShaderInfoLog);
+                return Err(format!("{}: failed to compile:\n{}", name, log));
+            }
+        }
+        Ok(shader)
+    }
+}
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteShader(self.id);
+        }
+    }
+}
+
+pub struct ShaderProgram {
+    id: GLuint,
+}
+impl ShaderProgram {
+    // `name` is what errors call the program
+    fn link(shaders: &[Shader], name: &str) -> Result<ShaderProgram, String> {
+        let program = ShaderProgram { id: unsafe { gl::CreateProgram() } };
+        unsafe {
+            for shader in shaders {
+                gl::AttachShader(program.id, shader.id);
+            }
+            gl::LinkProgram(program.id);
+            for shader in shaders {
+                gl::DetachShader(program.id, shader.id);
+            }
+            let mut success: GLint = 1;
+            gl::GetProgramiv(program.id, gl::LINK_STATUS, &mut success);
+            if success == 0 {
+                let log = info_log(program.id, gl::GetProgramiv, gl::GetProgramInfoLog);
+                return Err(format!("{}: failed to link:\n{}", name, log));
+            }
+        }
+        Ok(program)
+    }
+
+    pub fn set_used(&self) {
+        unsafe {
+            gl::UseProgram(self.id);
+        }
+    }
+
+    pub fn uniform_location(&self, name: &str) -> GLint {
+        let name = CString::new(name).unwrap();
+        unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) }
+    }
+}
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+    }
+}
+
+// The vertex and fragment shader files a program is built from, and when
+// they were last built
+pub struct ProgramSource {
+    vert: PathBuf,
+    frag: PathBuf,
+    built: [Option<SystemTime>; 2],
+}
+impl ProgramSource {
+    // File names are relative to SHADER_DIR
+    pub fn new(vert: &str, frag: &str) -> ProgramSource {
+        let dir = Path::new(SHADER_DIR);
+        ProgramSource { vert: dir.join(vert), frag: dir.join(frag), built: [None, None] }
+    }
+
+    // Whether either file has changed on disk since the last build, failed or not
+    pub fn changed(&self) -> bool {
+        self.modified() != self.built
+    }
+
+    pub fn build(&mut self) -> Result<ShaderProgram, String> {
+        // Noted first, so a broken file isn't retried every frame until it's saved again
+        self.built = self.modified();
+        let shaders = [
+            Shader::from_file(&self.vert, gl::VERTEX_SHADER)?,
+            Shader::from_file(&self.frag, gl::FRAGMENT_SHADER)?,
+        ];
+        ShaderProgram::link(&shaders, &format!("{} + {}", self.vert.display(), self.frag.display()))
+    }
+
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        [modified(&self.vert), modified(&self.frag)]
+    }
+}
+
+// Builds a component's custom shader: the textured vertex shader, with
+// `source` as the body of its fragment shader. The prelude declares the inputs
+// and calls the guest's `vec4 effect(vec2 uv)`; `#line` keeps the compiler's
+// line numbers matching the guest's source.
+pub fn build_custom(source: &str) -> Result<ShaderProgram, String> {
+    if source.contains("#version") {
+        return Err("custom shader: no #version needed, the host supplies it".to_string());
+    }
+    let dir = Path::new(SHADER_DIR);
+    let prelude_path = dir.join("effect_prelude.frag");
+    let prelude = fs::read_to_string(&prelude_path)
+        .map_err(|e| format!("{}: {}", prelude_path.display(), e))?;
+    let shaders = [
+        Shader::from_file(&dir.join("textured.vert"), gl::VERTEX_SHADER)?,
+        Shader::from_source(&format!("{}\n#line 1\n{}", prelude, source), gl::FRAGMENT_SHADER, "custom shader")?,
+    ];
+    ShaderProgram::link(&shaders, "custom shader")
+}
+
+// Reads a shader or program's info log, using the matching pair of GL getters
+unsafe fn info_log(
+    id: GLuint,
+    get_iv: unsafe fn(GLuint, GLenum, *mut GLint),
+    get_log: unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut len: GLint = 0;
+    get_iv(id, gl::INFO_LOG_LENGTH, &mut len);
+    let mut buffer = vec![0u8; len.max(1) as usize];
+    let mut written: GLsizei = 0;
+    get_log(id, len, &mut written, buffer.as_mut_ptr() as *mut GLchar);
+    buffer.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&buffer).trim_end().to_string()
+}
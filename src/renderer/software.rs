@@ -0,0 +1,545 @@
+// Pure-Rust rasterizer drawing into an in-memory framebuffer. Needs no GPU or
+// display, and follows the GL backend's conventions: pixel centers at +0.5,
+// premultiplied blending, and the same 8px transparency checkerboard.
+
+use std::collections::HashMap;
+
+use crate::math::Mat4;
+use crate::shapes::Vertex;
+use super::{Quad, Renderer, Sampling, ShaderUse, Viewport};
+
+const CHECK_SIZE: i32 = 8;
+
+// An image, rows bottom-up. Uploaded images are kept in the guest pixel
+// format; render targets and the screen hold premultiplied pixels.
+struct SoftImage {
+    w: i32,
+    h: i32,
+    pixels: Vec<u8>,
+    // Bilinear rather than nearest. Mipmapped sampling is bilinear here too.
+    smooth: bool,
+    premultiplied: bool,
+    // Drawing is limited to this, when set
+    scissor: Option<Viewport>,
+}
+
+pub struct SoftwareRenderer {
+    screen: SoftImage,
+    images: HashMap<u32, SoftImage>,
+    // The render target being drawn to, taken out of `images` while it's bound,
+    // so it can be drawn into while other images are sampled
+    target: Option<(u32, SoftImage)>,
+    next_image: u32,
+    // Custom shaders are GLSL, so they draw with the default shading here. Said once.
+    warned_shaders: bool,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> SoftwareRenderer {
+        SoftwareRenderer {
+            screen: SoftImage::blank(width as i32, height as i32, true),
+            images: HashMap::new(),
+            target: None,
+            // 0 stays invalid, as with GL textures
+            next_image: 1,
+            warned_shaders: false,
+        }
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_image;
+        self.next_image += 1;
+        id
+    }
+
+    fn surface(&mut self) -> &mut SoftImage {
+        match &mut self.target {
+            Some((_, image)) => image,
+            None => &mut self.screen,
+        }
+    }
+
+    fn unbind_target(&mut self) {
+        if let Some((id, image)) = self.target.take() {
+            self.images.insert(id, image);
+        }
+    }
+
+    fn draw_quad(&mut self, id: u32, transform: &Mat4, source_rect: [f32; 4], tint: [f32; 4], checkerboard: bool) {
+        let surface = match &mut self.target {
+            Some((_, image)) => image,
+            None => &mut self.screen,
+        };
+        // Drawing a target into itself comes out here too, since it's not in `images` while bound
+        let image = match self.images.get(&id) {
+            Some(image) if image.w > 0 && image.h > 0 => image,
+            _ => return,
+        };
+        // The affine part of the transform, unit quad to pixels:
+        //   px = a * lx + c * ly + tx
+        //   py = b * lx + d * ly + ty
+        let (a, b, c, d, tx, ty) = (transform[0], transform[1], transform[4], transform[5], transform[12], transform[13]);
+        let det = a * d - b * c;
+        if det == 0.0 {
+            return;
+        }
+        let corners = [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)]
+            .iter()
+            .map(|&(lx, ly)| (a * lx + c * ly + tx, b * lx + d * ly + ty))
+            .collect::<Vec<(f32, f32)>>();
+        let min_x = corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+        let min_y = corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+        let max_x = corners.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let max_y = corners.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+        let (x0, y0, x1, y1) = surface.clip(min_x, min_y, max_x, max_y);
+        let tint = [tint[0] * tint[3], tint[1] * tint[3], tint[2] * tint[3], tint[3]];
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                // Back from the pixel center to the unit quad
+                let (cx, cy) = (px as f32 + 0.5 - tx, py as f32 + 0.5 - ty);
+                let lx = (d * cx - c * cy) / det;
+                let ly = (a * cy - b * cx) / det;
+                if lx < -0.5 || lx >= 0.5 || ly < -0.5 || ly >= 0.5 {
+                    continue;
+                }
+                let u = source_rect[0] + (lx + 0.5) * source_rect[2];
+                let v = source_rect[1] + (ly + 0.5) * source_rect[3];
+                let mut color = image.sample(u, v);
+                if !image.premultiplied {
+                    color = [color[0] * color[3], color[1] * color[3], color[2] * color[3], color[3]];
+                }
+                for (channel, tint) in color.iter_mut().zip(tint.iter()) {
+                    *channel *= tint;
+                }
+                if checkerboard {
+                    let cell = px.div_euclid(CHECK_SIZE) + py.div_euclid(CHECK_SIZE);
+                    let check = if cell % 2 == 0 { 0.8 } else { 0.6 };
+                    let alpha = color[3];
+                    for channel in color.iter_mut().take(3) {
+                        *channel += check * (1.0 - alpha);
+                    }
+                    color[3] = 1.0;
+                }
+                surface.blend(px, py, color);
+            }
+        }
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn size(&self) -> (u32, u32) {
+        (self.screen.w as u32, self.screen.h as u32)
+    }
+
+    fn drawable_size(&self) -> (u32, u32) {
+        self.size()
+    }
+
+    fn begin_frame(&mut self, clear_color: [f32; 4]) {
+        self.unbind_target();
+        self.clear(clear_color);
+    }
+
+    fn end_frame(&mut self) {}
+
+    fn alloc_image(&mut self) -> u32 {
+        let id = self.next_id();
+        self.images.insert(id, SoftImage::blank(0, 0, false));
+        id
+    }
+
+    fn alloc_target(&mut self, w: i32, h: i32) -> u32 {
+        let id = self.next_id();
+        self.images.insert(id, SoftImage::blank(w, h, true));
+        id
+    }
+
+    fn free_image(&mut self, id: u32) {
+        if self.target.as_ref().map_or(false, |(target, _)| *target == id) {
+            self.target = None;
+        }
+        self.images.remove(&id);
+    }
+
+    fn upload_image(&mut self, id: u32, pixels: &[u8], w: i32, h: i32) {
+        if let Some(image) = self.images.get_mut(&id) {
+            let smooth = image.smooth;
+            *image = SoftImage { w, h, pixels: pixels.to_vec(), smooth, premultiplied: false, scissor: None };
+        }
+    }
+
+    fn upload_region(&mut self, id: u32, pixels: &[u8], tex_w: i32, x0: i32, y0: i32, x1: i32, y1: i32) {
+        let image = match self.images.get_mut(&id) {
+            Some(image) => image,
+            None => return,
+        };
+        // Like GL, regions outside the image are an error; drop them rather than panic
+        if x1 > image.w || y1 > image.h {
+            println!("Dropping region upload outside of image {}", id);
+            return;
+        }
+        for y in y0..y1 {
+            let src = ((y * tex_w + x0) * 4) as usize;
+            let dst = ((y * image.w + x0) * 4) as usize;
+            let len = ((x1 - x0) * 4) as usize;
+            image.pixels[dst..dst + len].copy_from_slice(&pixels[src..src + len]);
+        }
+    }
+
+    fn set_sampling(&mut self, id: u32, sampling: Sampling) {
+        let image = match &mut self.target {
+            Some((target, image)) if *target == id => Some(image),
+            _ => self.images.get_mut(&id),
+        };
+        if let Some(image) = image {
+            image.smooth = sampling != Sampling::Nearest;
+        }
+    }
+
+    fn set_target(&mut self, target: Option<u32>) -> bool {
+        if target == self.target.as_ref().map(|(id, _)| *id) {
+            return true;
+        }
+        match target {
+            Some(id) if !self.images.get(&id).map_or(false, |image| image.premultiplied) => false,
+            Some(id) => {
+                self.unbind_target();
+                let image = self.images.remove(&id).unwrap();
+                self.target = Some((id, image));
+                true
+            },
+            None => {
+                self.unbind_target();
+                true
+            },
+        }
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        let [r, g, b, a] = color;
+        let pixel = [to_byte(r * a), to_byte(g * a), to_byte(b * a), to_byte(a)];
+        let surface = self.surface();
+        let (x0, y0, x1, y1) = surface.clip(0.0, 0.0, surface.w as f32, surface.h as f32);
+        if x1 <= x0 {
+            return;
+        }
+        for y in y0..y1 {
+            let row = ((y * surface.w + x0) * 4) as usize..((y * surface.w + x1) * 4) as usize;
+            for dst in surface.pixels[row].chunks_exact_mut(4) {
+                dst.copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    fn set_clip(&mut self, clip: Option<Viewport>) {
+        self.surface().scissor = clip;
+    }
+
+    fn is_target(&self, id: u32) -> bool {
+        match &self.target {
+            Some((target, _)) if *target == id => true,
+            _ => self.images.get(&id).map_or(false, |image| image.premultiplied),
+        }
+    }
+
+    fn create_shader(&mut self, _source: &str) -> Result<u32, String> {
+        if !self.warned_shaders {
+            println!("The software renderer can't run custom shaders, drawing without them");
+            self.warned_shaders = true;
+        }
+        Ok(self.next_id())
+    }
+
+    fn free_shader(&mut self, _id: u32) {}
+
+    fn draw_quads(&mut self, id: u32, quads: &[Quad], checkerboard: bool, _shader: Option<&ShaderUse>) {
+        for quad in quads {
+            self.draw_quad(id, &quad.transform, quad.source_rect, quad.tint, checkerboard);
+        }
+    }
+
+    fn draw_triangles(&mut self, vertices: &[Vertex]) {
+        let surface = self.surface();
+        for tri in vertices.chunks_exact(3) {
+            surface.fill_triangle(tri[0], tri[1], tri[2]);
+        }
+    }
+
+    fn post_pass(&mut self, _shader: &ShaderUse) {}
+
+    fn read_pixels(&mut self) -> Vec<u8> {
+        self.screen.pixels.clone()
+    }
+
+    fn read_image(&mut self, id: u32) -> Option<(i32, i32, Vec<u8>)> {
+        let image = match &self.target {
+            Some((target, image)) if *target == id => image,
+            _ => self.images.get(&id)?,
+        };
+        let mut pixels = image.pixels.clone();
+        if image.premultiplied {
+            super::unpremultiply(&mut pixels);
+        }
+        Some((image.w, image.h, pixels))
+    }
+}
+
+impl SoftImage {
+    fn blank(w: i32, h: i32, premultiplied: bool) -> SoftImage {
+        let len = (w.max(0) as usize).checked_mul(h.max(0) as usize).and_then(|n| n.checked_mul(4))
+            .expect("image size overflows");
+        SoftImage { w, h, pixels: vec![0; len], smooth: false, premultiplied, scissor: None }
+    }
+
+    fn texel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = x.max(0).min(self.w - 1);
+        let y = y.max(0).min(self.h - 1);
+        let i = ((y * self.w + x) * 4) as usize;
+        let p = &self.pixels[i..i + 4];
+        [p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0, p[3] as f32 / 255.0]
+    }
+
+    // Edges clamped, in whichever alpha the image is stored with
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let (x, y) = (u * self.w as f32, v * self.h as f32);
+        if !self.smooth {
+            return self.texel(x.floor() as i32, y.floor() as i32);
+        }
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let (t00, t10) = (self.texel(x0, y0), self.texel(x0 + 1, y0));
+        let (t01, t11) = (self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1));
+        let mut out = [0.0; 4];
+        for (c, out) in out.iter_mut().enumerate() {
+            let bottom = t00[c] + (t10[c] - t00[c]) * fx;
+            let top = t01[c] + (t11[c] - t01[c]) * fx;
+            *out = bottom + (top - bottom) * fy;
+        }
+        out
+    }
+
+    // Clips a pixel-space bounding box to the image and scissor, as a half-open pixel range
+    fn clip(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> (i32, i32, i32, i32) {
+        let (mut x0, mut y0, mut x1, mut y1) = (0, 0, self.w, self.h);
+        if let Some(s) = self.scissor {
+            x0 = s.x.max(0);
+            y0 = s.y.max(0);
+            x1 = (s.x + s.w).min(self.w);
+            y1 = (s.y + s.h).min(self.h);
+        }
+        (
+            (min_x.floor() as i32).max(x0),
+            (min_y.floor() as i32).max(y0),
+            (max_x.ceil() as i32).min(x1),
+            (max_y.ceil() as i32).min(y1),
+        )
+    }
+
+    // Blends a premultiplied color over one pixel
+    fn blend(&mut self, x: i32, y: i32, color: [f32; 4]) {
+        let i = ((y * self.w + x) * 4) as usize;
+        for (dst, src) in self.pixels[i..i + 4].iter_mut().zip(color.iter()) {
+            *dst = to_byte(src + *dst as f32 / 255.0 * (1.0 - color[3]));
+        }
+    }
+
+    fn fill_triangle(&mut self, v0: Vertex, mut v1: Vertex, mut v2: Vertex) {
+        let mut area = edge(&v0, &v1, v2.x, v2.y);
+        if area == 0.0 {
+            return;
+        }
+        // Keep everything counter-clockwise, so the inside is where all edges are positive
+        if area < 0.0 {
+            std::mem::swap(&mut v1, &mut v2);
+            area = -area;
+        }
+        let (x0, y0, x1, y1) = self.clip(
+            v0.x.min(v1.x).min(v2.x), v0.y.min(v1.y).min(v2.y),
+            v0.x.max(v1.x).max(v2.x), v0.y.max(v1.y).max(v2.y));
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+                let w0 = edge(&v1, &v2, cx, cy);
+                let w1 = edge(&v2, &v0, cx, cy);
+                let w2 = edge(&v0, &v1, cx, cy);
+                if !(covers(w0, &v1, &v2) && covers(w1, &v2, &v0) && covers(w2, &v0, &v1)) {
+                    continue;
+                }
+                let mut color = [0.0; 4];
+                for (c, out) in color.iter_mut().enumerate() {
+                    *out = (w0 * v0.color[c] + w1 * v1.color[c] + w2 * v2.color[c]) / area;
+                }
+                self.blend(px, py, color);
+            }
+        }
+    }
+}
+
+// Twice the signed area of (a, b, p); positive when p is left of a -> b
+fn edge(a: &Vertex, b: &Vertex, px: f32, py: f32) -> f32 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+// Pixels exactly on an edge belong to only one of the two triangles sharing
+// it, so seams in translucent shapes don't get blended twice
+fn covers(w: f32, a: &Vertex, b: &Vertex) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    w > 0.0 || (w == 0.0 && (dy < 0.0 || (dy == 0.0 && dx > 0.0)))
+}
+
+fn to_byte(v: f32) -> u8 {
+    (v.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn pixel(pixels: &[u8], w: i32, x: i32, y: i32) -> [u8; 4] {
+        let i = ((y * w + x) * 4) as usize;
+        [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]]
+    }
+
+    fn vertex(x: f32, y: f32, color: [f32; 4]) -> Vertex {
+        Vertex { x, y, color }
+    }
+
+    #[test]
+    fn fill_triangle_covers_pixel_centers() {
+        let mut image = SoftImage::blank(4, 4, true);
+        let red = [1.0, 0.0, 0.0, 1.0];
+        // Clockwise, which gets flipped around
+        image.fill_triangle(vertex(0.0, 0.0, red), vertex(0.0, 3.0, red), vertex(3.0, 0.0, red));
+        for y in 0..4 {
+            for x in 0..4 {
+                // Centers at (x + 0.5, y + 0.5), inside where x + y + 1 < 3
+                let expected = if x + y < 2 { RED } else { CLEAR };
+                assert_eq!(pixel(&image.pixels, 4, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_blend_once() {
+        let mut image = SoftImage::blank(2, 2, true);
+        let half = [0.5, 0.0, 0.0, 0.5];
+        // Two halves of the 2x2 square, split along the diagonal through the pixel centers
+        image.fill_triangle(vertex(0.0, 0.0, half), vertex(2.0, 0.0, half), vertex(2.0, 2.0, half));
+        image.fill_triangle(vertex(0.0, 0.0, half), vertex(2.0, 2.0, half), vertex(0.0, 2.0, half));
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(pixel(&image.pixels, 2, x, y), [128, 0, 0, 128], "({}, {})", x, y);
+            }
+        }
+    }
+
+    // A 2x1 image, red then green
+    fn red_green(r: &mut SoftwareRenderer) -> u32 {
+        let id = r.alloc_image();
+        let pixels: Vec<u8> = RED.iter().chain(GREEN.iter()).copied().collect();
+        r.upload_image(id, &pixels, 2, 1);
+        id
+    }
+
+    fn quad(rotation: f32, source_rect: [f32; 4]) -> Quad {
+        Quad { transform: math::rect_transform(0.0, 0.0, 4.0, 4.0, 1.0, rotation), source_rect, tint: [1.0; 4] }
+    }
+
+    #[test]
+    fn draw_quad_samples_source_rect() {
+        let mut r = SoftwareRenderer::new(4, 4);
+        let id = red_green(&mut r);
+        r.draw_quads(id, &[quad(0.0, [0.0, 0.0, 1.0, 1.0])], false, None);
+        let pixels = r.read_pixels();
+        for y in 0..4 {
+            assert_eq!(pixel(&pixels, 4, 0, y), RED);
+            assert_eq!(pixel(&pixels, 4, 1, y), RED);
+            assert_eq!(pixel(&pixels, 4, 2, y), GREEN);
+            assert_eq!(pixel(&pixels, 4, 3, y), GREEN);
+        }
+
+        // Just the right half of the image
+        r.draw_quads(id, &[quad(0.0, [0.5, 0.0, 0.5, 1.0])], false, None);
+        let pixels = r.read_pixels();
+        for x in 0..4 {
+            assert_eq!(pixel(&pixels, 4, x, 0), GREEN);
+        }
+    }
+
+    #[test]
+    fn draw_quad_rotates() {
+        let mut r = SoftwareRenderer::new(4, 4);
+        let id = red_green(&mut r);
+        // Half a turn around the center swaps the sides
+        r.draw_quads(id, &[quad(std::f32::consts::PI, [0.0, 0.0, 1.0, 1.0])], false, None);
+        let pixels = r.read_pixels();
+        for y in 0..4 {
+            assert_eq!(pixel(&pixels, 4, 0, y), GREEN);
+            assert_eq!(pixel(&pixels, 4, 3, y), RED);
+        }
+
+        // A quarter turn counter-clockwise puts the left side at the bottom
+        let mut r = SoftwareRenderer::new(4, 4);
+        let id = red_green(&mut r);
+        r.draw_quads(id, &[quad(std::f32::consts::FRAC_PI_2, [0.0, 0.0, 1.0, 1.0])], false, None);
+        let pixels = r.read_pixels();
+        for x in 0..4 {
+            assert_eq!(pixel(&pixels, 4, x, 0), RED);
+            assert_eq!(pixel(&pixels, 4, x, 3), GREEN);
+        }
+    }
+
+    #[test]
+    fn blend_is_premultiplied() {
+        let mut image = SoftImage::blank(1, 1, true);
+        image.pixels.copy_from_slice(&[0, 0, 255, 255]);
+        // Half-transparent red over opaque blue
+        image.blend(0, 0, [0.5, 0.0, 0.0, 0.5]);
+        assert_eq!(pixel(&image.pixels, 1, 0, 0), [128, 0, 128, 255]);
+
+        // Fully transparent leaves it be
+        image.blend(0, 0, [0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(pixel(&image.pixels, 1, 0, 0), [128, 0, 128, 255]);
+    }
+
+    #[test]
+    fn clip_to_image_and_scissor() {
+        let mut image = SoftImage::blank(10, 10, true);
+        assert_eq!(image.clip(-5.0, -5.0, 20.0, 20.0), (0, 0, 10, 10));
+        assert_eq!(image.clip(1.5, 2.5, 3.2, 4.0), (1, 2, 4, 4));
+
+        image.scissor = Some(Viewport { x: 2, y: 3, w: 4, h: 20 });
+        assert_eq!(image.clip(0.0, 0.0, 10.0, 10.0), (2, 3, 6, 10));
+        assert_eq!(image.clip(-1.5, 4.2, 3.1, 5.0), (2, 4, 4, 5));
+        // Entirely outside comes out empty
+        let (x0, _, x1, _) = image.clip(7.0, 0.0, 9.0, 10.0);
+        assert!(x1 <= x0);
+    }
+
+    #[test]
+    fn clear_inside_clip() {
+        let mut r = SoftwareRenderer::new(4, 4);
+        r.set_clip(Some(Viewport { x: 1, y: 1, w: 2, h: 2 }));
+        r.clear([1.0, 0.0, 0.0, 1.0]);
+        let pixels = r.read_pixels();
+        for y in 0..4 {
+            for x in 0..4 {
+                let inside = (1..3).contains(&x) && (1..3).contains(&y);
+                assert_eq!(pixel(&pixels, 4, x, y), if inside { RED } else { CLEAR }, "({}, {})", x, y);
+            }
+        }
+
+        // And the whole screen once the clip's gone
+        r.set_clip(None);
+        r.clear([0.0, 1.0, 0.0, 1.0]);
+        assert!(r.read_pixels().chunks_exact(4).all(|p| p == GREEN));
+    }
+}
@@ -0,0 +1,233 @@
+// Tessellates primitive shapes into colored triangles, in pixel space
+
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Vertex {
+    pub x: f32,
+    pub y: f32,
+    // Premultiplied
+    pub color: [f32; 4],
+}
+
+// Triangles waiting to be drawn, three vertices each
+#[derive(Default)]
+pub struct ShapeBatch {
+    pub vertices: Vec<Vertex>,
+}
+
+// Takes a straight-alpha color, as passed by guests
+fn premultiply(color: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    [r * a, g * a, b * a, a]
+}
+
+// Enough segments that each is a few pixels long
+fn circle_segments(radius: f32) -> usize {
+    ((radius * 2.0 * PI / 4.0) as usize).max(12).min(256)
+}
+
+impl ShapeBatch {
+    fn triangle(&mut self, points: [(f32, f32); 3], color: [f32; 4]) {
+        for &(x, y) in points.iter() {
+            self.vertices.push(Vertex { x, y, color });
+        }
+    }
+
+    fn quad(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), d: (f32, f32), color: [f32; 4]) {
+        self.triangle([a, b, c], color);
+        self.triangle([c, d, a], color);
+    }
+
+    // Empty shapes below add no triangles rather than zero-area ones
+    pub fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        if w == 0.0 || h == 0.0 {
+            return;
+        }
+        let color = premultiply(color);
+        self.quad((x, y), (x + w, y), (x + w, y + h), (x, y + h), color);
+    }
+
+    // The outline is drawn inside the rect
+    pub fn stroke_rect(&mut self, x: f32, y: f32, w: f32, h: f32, width: f32, color: [f32; 4]) {
+        if width <= 0.0 {
+            return;
+        }
+        let t = width.min(w / 2.0).min(h / 2.0);
+        self.fill_rect(x, y, w, t, color);
+        self.fill_rect(x, y + h - t, w, t, color);
+        self.fill_rect(x, y + t, t, h - 2.0 * t, color);
+        self.fill_rect(x + w - t, y + t, t, h - 2.0 * t, color);
+    }
+
+    pub fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: [f32; 4]) {
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 || width <= 0.0 {
+            return;
+        }
+        // Perpendicular, half the width long
+        let (nx, ny) = (-dy / len * width / 2.0, dx / len * width / 2.0);
+        let color = premultiply(color);
+        self.quad((x0 + nx, y0 + ny), (x0 - nx, y0 - ny), (x1 - nx, y1 - ny), (x1 + nx, y1 + ny), color);
+    }
+
+    pub fn fill_circle(&mut self, cx: f32, cy: f32, radius: f32, color: [f32; 4]) {
+        if radius <= 0.0 {
+            return;
+        }
+        let color = premultiply(color);
+        let segments = circle_segments(radius);
+        let point = |i: usize| {
+            let angle = i as f32 / segments as f32 * 2.0 * PI;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        };
+        for i in 0..segments {
+            self.triangle([(cx, cy), point(i), point(i + 1)], color);
+        }
+    }
+
+    // The ring is centered on the circle's edge
+    pub fn stroke_circle(&mut self, cx: f32, cy: f32, radius: f32, width: f32, color: [f32; 4]) {
+        if width <= 0.0 {
+            return;
+        }
+        let color = premultiply(color);
+        let segments = circle_segments(radius);
+        let (inner, outer) = ((radius - width / 2.0).max(0.0), radius + width / 2.0);
+        let point = |i: usize, r: f32| {
+            let angle = i as f32 / segments as f32 * 2.0 * PI;
+            (cx + r * angle.cos(), cy + r * angle.sin())
+        };
+        for i in 0..segments {
+            self.quad(point(i, inner), point(i, outer), point(i + 1, outer), point(i + 1, inner), color);
+        }
+    }
+
+    // Joints are filled with a circle, so corners stay solid at any angle
+    pub fn polyline(&mut self, points: &[(f32, f32)], width: f32, closed: bool, color: [f32; 4]) {
+        if points.len() < 2 {
+            return;
+        }
+        let mut segments: Vec<((f32, f32), (f32, f32))> = points.windows(2).map(|w| (w[0], w[1])).collect();
+        if closed {
+            segments.push((points[points.len() - 1], points[0]));
+        }
+        for &((x0, y0), (x1, y1)) in &segments {
+            self.line(x0, y0, x1, y1, width, color);
+        }
+        let joints = if closed { &points[..] } else { &points[1..points.len() - 1] };
+        for &(x, y) in joints {
+            self.fill_circle(x, y, width / 2.0, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 0.5];
+
+    // Total area of the batch's triangles
+    fn area(batch: &ShapeBatch) -> f32 {
+        batch.vertices.chunks_exact(3)
+            .map(|t| ((t[1].x - t[0].x) * (t[2].y - t[0].y) - (t[2].x - t[0].x) * (t[1].y - t[0].y)).abs() / 2.0)
+            .sum()
+    }
+
+    // (min x, min y, max x, max y) of the batch's vertices
+    fn bounds(batch: &ShapeBatch) -> (f32, f32, f32, f32) {
+        batch.vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(x0, y0, x1, y1), v| {
+            (x0.min(v.x), y0.min(v.y), x1.max(v.x), y1.max(v.y))
+        })
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn fills_rects() {
+        let mut batch = ShapeBatch::default();
+        batch.fill_rect(10.0, 20.0, 30.0, 40.0, RED);
+        assert_eq!(batch.vertices.len(), 6);
+        assert_eq!(bounds(&batch), (10.0, 20.0, 40.0, 60.0));
+        assert_eq!(area(&batch), 1200.0);
+        assert!(batch.vertices.iter().all(|v| v.color == [0.5, 0.0, 0.0, 0.5]));
+    }
+
+    #[test]
+    fn strokes_rects_inside() {
+        let mut batch = ShapeBatch::default();
+        batch.stroke_rect(0.0, 0.0, 10.0, 20.0, 2.0, RED);
+        assert_eq!(batch.vertices.len(), 24);
+        assert_eq!(bounds(&batch), (0.0, 0.0, 10.0, 20.0));
+        // Everything but the 6x16 middle
+        assert_eq!(area(&batch), 200.0 - 96.0);
+
+        // Wider than the rect fills it, without overlap
+        let mut batch = ShapeBatch::default();
+        batch.stroke_rect(0.0, 0.0, 10.0, 20.0, 8.0, RED);
+        assert_eq!(bounds(&batch), (0.0, 0.0, 10.0, 20.0));
+        assert_eq!(area(&batch), 200.0);
+    }
+
+    #[test]
+    fn strokes_lines_around_their_center() {
+        let mut batch = ShapeBatch::default();
+        batch.line(0.0, 10.0, 30.0, 10.0, 4.0, RED);
+        assert_eq!(bounds(&batch), (0.0, 8.0, 30.0, 12.0));
+        assert_eq!(area(&batch), 120.0);
+
+        let mut batch = ShapeBatch::default();
+        batch.line(0.0, 0.0, 30.0, 40.0, 2.0, RED);
+        assert!(close(area(&batch), 100.0));
+    }
+
+    #[test]
+    fn strokes_circles_on_their_edge() {
+        let mut batch = ShapeBatch::default();
+        batch.stroke_circle(0.0, 0.0, 10.0, 4.0, RED);
+        assert!(batch.vertices.iter().all(|v| {
+            let r = (v.x * v.x + v.y * v.y).sqrt();
+            close(r, 8.0) || close(r, 12.0)
+        }));
+        // Slightly under the true ring, since the edges are chords
+        let ring = PI * (12.0 * 12.0 - 8.0 * 8.0);
+        assert!(area(&batch) < ring && area(&batch) > ring * 0.95);
+    }
+
+    #[test]
+    fn joins_polylines() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let mut open = ShapeBatch::default();
+        open.polyline(&points, 2.0, false, RED);
+        let mut closed = ShapeBatch::default();
+        closed.polyline(&points, 2.0, true, RED);
+        // Two lines and a joint, then a third line and a joint at every point
+        let joint = circle_segments(1.0) * 3;
+        assert_eq!(open.vertices.len(), 2 * 6 + joint);
+        assert_eq!(closed.vertices.len(), 3 * 6 + 3 * joint);
+    }
+
+    #[test]
+    fn skips_empty_shapes() {
+        let mut batch = ShapeBatch::default();
+        batch.fill_rect(5.0, 5.0, 0.0, 10.0, RED);
+        batch.fill_rect(5.0, 5.0, 10.0, 0.0, RED);
+        batch.stroke_rect(5.0, 5.0, 0.0, 0.0, 2.0, RED);
+        batch.stroke_rect(5.0, 5.0, 10.0, 10.0, 0.0, RED);
+        batch.line(5.0, 5.0, 5.0, 5.0, 2.0, RED);
+        batch.line(0.0, 0.0, 10.0, 10.0, 0.0, RED);
+        batch.fill_circle(5.0, 5.0, 0.0, RED);
+        batch.stroke_circle(5.0, 5.0, 10.0, 0.0, RED);
+        batch.polyline(&[(1.0, 1.0)], 2.0, true, RED);
+        batch.polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], 0.0, true, RED);
+        assert!(batch.vertices.is_empty());
+        // A zero-size stroke_circle is still a dot
+        batch.stroke_circle(5.0, 5.0, 0.0, 2.0, RED);
+        assert!(area(&batch) > 0.0);
+    }
+}
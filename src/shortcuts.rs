@@ -0,0 +1,560 @@
+// Keyboard shortcuts: named commands, the key bindings that trigger them, and
+// the "commands" imports
+//
+// Commands are registered by name, by the host (app.*) and by components, who
+// poll whether they went off this frame. Which keys trigger them comes from a
+// bindings file the user can edit. The host's commands go off whatever has
+// focus, a component's only while it has focus, so its bindings don't take
+// keys from other components. Keys that go to a shortcut don't reach any
+// component as key or text input.
+
+use anyhow::{Result, bail, format_err};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use std::{
+    cell::RefCell,
+    fs,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
+
+use wasmtime::*;
+
+use crate::{
+    component::{Component, ImportModule},
+    renderer,
+};
+
+pub const BINDINGS_PATH: &str = "resources/keybindings.txt";
+// Used when the bindings file can't be read
+const DEFAULT_BINDINGS: &str = include_str!("../resources/keybindings.txt");
+// How long a sequence waits on its next chord before starting over
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Modifier bits of a chord, left and right counting the same
+const CTRL: u8 = 1;
+const SHIFT: u8 = 2;
+const ALT: u8 = 4;
+const GUI: u8 = 8;
+
+// A key pressed with some set of modifiers held
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Chord {
+    keycode: Keycode,
+    mods: u8,
+}
+impl Chord {
+    // Ctrl+Shift+Z, say. Keycodes go by what the key types in the current layout.
+    fn parse(text: &str) -> Option<Chord> {
+        // "+" and "Ctrl++" are the plus key
+        let split = match text.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => text.len() - 1,
+            _ => text.rfind('+').map_or(0, |i| i + 1),
+        };
+        let (mods_text, key) = text.split_at(split);
+        let mut mods = 0;
+        for name in mods_text.split('+').filter(|name| !name.is_empty()) {
+            mods |= match name.to_lowercase().as_str() {
+                "ctrl" | "control" => CTRL,
+                "shift" => SHIFT,
+                "alt" | "option" => ALT,
+                "gui" | "cmd" | "super" | "win" | "meta" => GUI,
+                _ => return None,
+            };
+        }
+        let keycode = Keycode::from_name(&key.replace('_', " "))?;
+        Some(Chord { keycode, mods })
+    }
+
+    fn from_event(keycode: Keycode, keymod: Mod) -> Chord {
+        let mut mods = 0;
+        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) { mods |= CTRL; }
+        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) { mods |= SHIFT; }
+        if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) { mods |= ALT; }
+        if keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD) { mods |= GUI; }
+        Chord { keycode, mods }
+    }
+}
+
+fn is_modifier(keycode: Keycode) -> bool {
+    matches!(keycode,
+        Keycode::LCtrl | Keycode::RCtrl | Keycode::LShift | Keycode::RShift |
+        Keycode::LAlt | Keycode::RAlt | Keycode::LGui | Keycode::RGui | Keycode::Mode)
+}
+
+struct Binding {
+    // Chords pressed one after another
+    sequence: Vec<Chord>,
+    command: String,
+    // As written, and where, for listing conflicts
+    keys: String,
+    line: usize,
+}
+
+// Format, one binding per line, '#' starts a comment:
+//     <chord> [<chord> ...] -> <command>
+fn parse_bindings(text: &str, path: &str) -> Result<Vec<Binding>> {
+    let mut bindings = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {},
+            [keys @ .., "->", command] if !keys.is_empty() => {
+                let sequence = keys.iter()
+                    .map(|key| Chord::parse(key).ok_or(format_err!("{}:{}: Unrecognized key: {}", path, i + 1, key)))
+                    .collect::<Result<Vec<Chord>>>()?;
+                bindings.push(Binding { sequence, command: command.to_string(), keys: keys.join(" "), line: i + 1 });
+            },
+            _ => bail!("{}:{}: Expected <keys> -> <command>: {}", path, i + 1, line),
+        }
+    }
+    Ok(bindings)
+}
+
+struct Command {
+    name: String,
+    // Times it went off since the start of the frame
+    triggered: u32,
+    // Registered by the host
+    global: bool,
+    // Components that registered it
+    components: Vec<Weak<RefCell<Component>>>,
+}
+impl Command {
+    // Whether its keys are live while `focus` has the keyboard
+    fn is_live(&self, focus: Option<&Rc<RefCell<Component>>>) -> bool {
+        self.global || focus.map_or(false, |focus| {
+            let focus = Rc::downgrade(focus);
+            self.components.iter().any(|component| component.ptr_eq(&focus))
+        })
+    }
+}
+
+enum Resolution {
+    Command(usize),
+    // The start of a longer sequence, waiting on the rest
+    Prefix,
+    None,
+}
+
+#[derive(Default)]
+struct Shortcuts {
+    bindings: Vec<Binding>,
+    path: String,
+    // Index + 1 is the command's id
+    commands: Vec<Command>,
+    // The sequence typed so far, and when its last chord was
+    pending: Vec<Chord>,
+    pending_at: Option<Instant>,
+    // Keys that went to a shortcut and are still down, so their repeats and
+    // releases don't reach components either
+    held: Vec<Scancode>,
+    // A shortcut just took the key down, so the text it types goes nowhere
+    swallow_text: bool,
+}
+thread_local! {
+    static SHORTCUTS: RefCell<Shortcuts> = RefCell::new(Shortcuts::default());
+}
+
+impl Shortcuts {
+    fn command(&self, name: &str) -> Option<usize> {
+        self.commands.iter().position(|command| command.name == name)
+    }
+
+    // For the host when `component` is None. Returns the command's id.
+    fn register(&mut self, name: &str, component: Option<&Rc<RefCell<Component>>>) -> u32 {
+        let index = self.command(name).unwrap_or_else(|| {
+            self.commands.push(Command { name: name.to_string(), triggered: 0, global: false, components: Vec::new() });
+            self.commands.len() - 1
+        });
+        let command = &mut self.commands[index];
+        match component {
+            Some(component) => {
+                let component = Rc::downgrade(component);
+                if !command.components.iter().any(|registered| registered.ptr_eq(&component)) {
+                    command.components.push(component);
+                }
+            },
+            None => command.global = true,
+        }
+        index as u32 + 1
+    }
+
+    // Only bindings to commands live for `focus` count, so keys bound to
+    // nothing it offers still reach it
+    fn resolve(&self, keys: &[Chord], focus: Option<&Rc<RefCell<Component>>>) -> Resolution {
+        let mut prefix = false;
+        for binding in &self.bindings {
+            let command = match self.command(&binding.command) {
+                Some(command) if self.commands[command].is_live(focus) => command,
+                _ => continue,
+            };
+            if binding.sequence == keys {
+                return Resolution::Command(command);
+            }
+            prefix |= binding.sequence.starts_with(keys);
+        }
+        if prefix { Resolution::Prefix } else { Resolution::None }
+    }
+
+    fn focus_changed(&mut self) {
+        self.pending.clear();
+    }
+
+    fn key_down(&mut self, scancode: Scancode, chord: Chord, repeat: bool, focus: Option<&Rc<RefCell<Component>>>,
+            now: Instant) -> bool {
+        // Holding a shortcut down repeats it, if it's a single chord
+        if repeat {
+            if !self.held.contains(&scancode) {
+                return false;
+            }
+            if let Resolution::Command(command) = self.resolve(&[chord], focus) {
+                self.commands[command].triggered += 1;
+            }
+            return true;
+        }
+        if self.pending_at.map_or(false, |at| now.duration_since(at) >= SEQUENCE_TIMEOUT) {
+            self.pending.clear();
+        }
+        self.pending_at = Some(now);
+        self.pending.push(chord);
+        loop {
+            match self.resolve(&self.pending, focus) {
+                Resolution::Command(command) => {
+                    self.commands[command].triggered += 1;
+                    self.pending.clear();
+                    break;
+                },
+                Resolution::Prefix => break,
+                // A sequence that went nowhere. The keys before this one are
+                // dropped, but this one could start something else.
+                Resolution::None if self.pending.len() > 1 => {
+                    self.pending.drain(..self.pending.len() - 1);
+                },
+                Resolution::None => {
+                    self.pending.clear();
+                    return false;
+                },
+            }
+        }
+        self.held.push(scancode);
+        true
+    }
+
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, a) in self.bindings.iter().enumerate() {
+            for b in &self.bindings[i + 1..] {
+                if a.sequence == b.sequence {
+                    if a.command != b.command {
+                        conflicts.push(format!("{}:{}: {} ({}) can't go off, it's bound to {} on line {} too",
+                            self.path, b.line, b.keys, b.command, a.command, a.line));
+                    }
+                } else if b.sequence.starts_with(&a.sequence) || a.sequence.starts_with(&b.sequence) {
+                    let (short, long) = if a.sequence.len() < b.sequence.len() { (a, b) } else { (b, a) };
+                    conflicts.push(format!("{}:{}: {} ({}) can't go off, {} ({}, line {}) goes off first",
+                        self.path, long.line, long.keys, long.command, short.keys, short.command, short.line));
+                }
+            }
+        }
+        for binding in &self.bindings {
+            if self.command(&binding.command).is_none() {
+                conflicts.push(format!("{}:{}: {} is bound to {}, which nothing registered",
+                    self.path, binding.line, binding.keys, binding.command));
+            }
+        }
+        conflicts
+    }
+}
+
+// Reads the user's bindings, falling back to the defaults if there's no file
+// or it doesn't parse
+pub fn load_bindings(path: &str) -> Result<()> {
+    let default_path = "(default bindings)";
+    let parsed = fs::read_to_string(path)
+        .map_err(|e| format_err!("couldn't read {}: {}", path, e))
+        .and_then(|text| parse_bindings(&text, path));
+    let (bindings, path) = match parsed {
+        Ok(bindings) => (bindings, path),
+        Err(e) => {
+            println!("Using the default key bindings, {}", e);
+            (parse_bindings(DEFAULT_BINDINGS, default_path)?, default_path)
+        },
+    };
+    SHORTCUTS.with(|shortcuts| {
+        let mut shortcuts = shortcuts.borrow_mut();
+        shortcuts.bindings = bindings;
+        shortcuts.path = path.to_string();
+        shortcuts.pending.clear();
+    });
+    Ok(())
+}
+
+// Registers a host command, which goes off whatever has focus. Returns the
+// command's id. Registering a name again gets the same command, which then goes
+// off for everyone who registered it.
+pub fn register(name: &str) -> u32 {
+    SHORTCUTS.with(|shortcuts| shortcuts.borrow_mut().register(name, None))
+}
+
+// Times the command went off this frame, 0 for an unknown id
+pub fn triggered(id: u32) -> u32 {
+    SHORTCUTS.with(|shortcuts| {
+        let shortcuts = shortcuts.borrow();
+        id.checked_sub(1).and_then(|i| shortcuts.commands.get(i as usize)).map_or(0, |command| command.triggered)
+    })
+}
+
+// Forgets last frame's commands, ahead of this frame's input
+pub fn begin_frame() {
+    SHORTCUTS.with(|shortcuts| {
+        for command in &mut shortcuts.borrow_mut().commands {
+            command.triggered = 0;
+        }
+    });
+}
+
+// Whether a shortcut took the key, in which case components shouldn't see it.
+// `focus` is the component with the keyboard.
+pub fn key_down(scancode: Option<Scancode>, keycode: Option<Keycode>, keymod: Mod, repeat: bool,
+        focus: Option<&Rc<RefCell<Component>>>) -> bool {
+    let (scancode, keycode) = match (scancode, keycode) {
+        (Some(scancode), Some(keycode)) if !is_modifier(keycode) => (scancode, keycode),
+        _ => return false,
+    };
+    SHORTCUTS.with(|shortcuts| {
+        let mut shortcuts = shortcuts.borrow_mut();
+        let taken = shortcuts.key_down(scancode, Chord::from_event(keycode, keymod), repeat, focus, Instant::now());
+        shortcuts.swallow_text = taken;
+        taken
+    })
+}
+
+// Drops a half-typed sequence, so it can't finish in a component whose
+// bindings it wasn't started for
+pub fn focus_changed() {
+    SHORTCUTS.with(|shortcuts| shortcuts.borrow_mut().focus_changed());
+}
+
+pub fn key_up(scancode: Option<Scancode>) -> bool {
+    SHORTCUTS.with(|shortcuts| {
+        let mut shortcuts = shortcuts.borrow_mut();
+        shortcuts.swallow_text = false;
+        match shortcuts.held.iter().position(|&held| Some(held) == scancode) {
+            Some(i) => {
+                shortcuts.held.swap_remove(i);
+                true
+            },
+            None => false,
+        }
+    })
+}
+
+// Whether text input belongs to a key a shortcut took. SDL sends it between
+// the key's down and up.
+pub fn swallows_text() -> bool {
+    SHORTCUTS.with(|shortcuts| shortcuts.borrow().swallow_text)
+}
+
+// Lists bindings that can't work as written. Call once everything has had a
+// chance to register its commands.
+pub fn report_conflicts() {
+    for conflict in SHORTCUTS.with(|shortcuts| shortcuts.borrow().conflicts()) {
+        println!("Key bindings: {}", conflict);
+    }
+}
+
+pub fn import_module(component: &Rc<RefCell<Component>>) -> ImportModule {
+    let store = &component.borrow().store;
+    let mut ret = ImportModule::new();
+    // Returns the command's id. Names are namespaced by convention, as in canvas.undo.
+    {
+        let component_weak = Rc::downgrade(component);
+        ret.add_func("registerCommand", Func::wrap(&store, move |name_ptr: i32| -> Result<i32, Trap> {
+            let component_rc = component_weak.upgrade().unwrap();
+            let memory = component_rc.borrow().memory()?;
+            let name = renderer::read_string(&memory, name_ptr)?;
+            Ok(SHORTCUTS.with(|shortcuts| shortcuts.borrow_mut().register(&name, Some(&component_rc))) as i32)
+        }));
+    }
+    // Times the command went off since the last frame
+    ret.add_func("commandTriggered", Func::wrap(&store, |id: i32| triggered(id as u32) as i32));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(keycode: Keycode, mods: u8) -> Chord {
+        Chord { keycode, mods }
+    }
+
+    fn shortcuts(bindings: &str) -> Shortcuts {
+        Shortcuts { bindings: parse_bindings(bindings, "test").unwrap(), path: "test".to_string(), ..Shortcuts::default() }
+    }
+
+    fn component() -> Rc<RefCell<Component>> {
+        Component::init(&Store::default())
+    }
+
+    // Presses and releases `key`, returning whether a shortcut took it
+    fn press(shortcuts: &mut Shortcuts, key: Chord, focus: Option<&Rc<RefCell<Component>>>) -> bool {
+        let taken = shortcuts.key_down(Scancode::Space, key, false, focus, Instant::now());
+        shortcuts.held.clear();
+        taken
+    }
+
+    fn triggered(shortcuts: &Shortcuts) -> Vec<u32> {
+        shortcuts.commands.iter().map(|command| command.triggered).collect()
+    }
+
+    #[test]
+    fn parses_chords() {
+        assert!(Chord::parse("Ctrl+Shift+Z") == Some(chord(Keycode::Z, CTRL | SHIFT)));
+        assert!(Chord::parse("control+z") == Some(chord(Keycode::Z, CTRL)));
+        assert!(Chord::parse("Cmd+Alt+F5") == Some(chord(Keycode::F5, GUI | ALT)));
+        assert!(Chord::parse("Escape") == Some(chord(Keycode::Escape, 0)));
+        assert!(Chord::parse("Keypad_Enter") == Some(chord(Keycode::KpEnter, 0)));
+        assert!(Chord::parse("+") == Some(chord(Keycode::Plus, 0)));
+        assert!(Chord::parse("Ctrl++") == Some(chord(Keycode::Plus, CTRL)));
+        assert!(Chord::parse("Hyper+Z").is_none());
+        assert!(Chord::parse("Ctrl+").is_none());
+        assert!(Chord::parse("NotAKey").is_none());
+    }
+
+    #[test]
+    fn chords_from_events_ignore_sides() {
+        assert!(Chord::from_event(Keycode::Z, Mod::RCTRLMOD | Mod::LSHIFTMOD) == chord(Keycode::Z, CTRL | SHIFT));
+        assert!(Chord::from_event(Keycode::Z, Mod::NUMMOD) == chord(Keycode::Z, 0));
+    }
+
+    #[test]
+    fn parses_bindings() {
+        let bindings = parse_bindings("# Comment\n\nCtrl+K Ctrl+S -> app.save  # trailing\nF5 -> app.reload\n", "test").unwrap();
+        assert_eq!(bindings.len(), 2);
+        assert!(bindings[0].sequence == [chord(Keycode::K, CTRL), chord(Keycode::S, CTRL)]);
+        assert_eq!((bindings[0].command.as_str(), bindings[0].keys.as_str(), bindings[0].line), ("app.save", "Ctrl+K Ctrl+S", 3));
+        assert_eq!((bindings[1].command.as_str(), bindings[1].line), ("app.reload", 4));
+
+        let error = |text| parse_bindings(text, "keys.txt").err().unwrap().to_string();
+        assert_eq!(error("F5 -> app.reload\nCtrl+Nope -> x"), "keys.txt:2: Unrecognized key: Ctrl+Nope");
+        assert_eq!(error("-> app.reload"), "keys.txt:1: Expected <keys> -> <command>: -> app.reload");
+        assert_eq!(error("F5 app.reload"), "keys.txt:1: Expected <keys> -> <command>: F5 app.reload");
+        assert!(parse_bindings(DEFAULT_BINDINGS, "defaults").is_ok());
+    }
+
+    #[test]
+    fn resolves_registered_commands() {
+        let mut shortcuts = shortcuts("Ctrl+K Ctrl+S -> app.save\nF5 -> app.reload\nF6 -> app.unregistered");
+        let save = shortcuts.register("app.save", None) as usize - 1;
+        let reload = shortcuts.register("app.reload", None) as usize - 1;
+        assert!(matches!(shortcuts.resolve(&[chord(Keycode::F5, 0)], None), Resolution::Command(c) if c == reload));
+        assert!(matches!(shortcuts.resolve(&[chord(Keycode::K, CTRL)], None), Resolution::Prefix));
+        assert!(matches!(shortcuts.resolve(&[chord(Keycode::K, CTRL), chord(Keycode::S, CTRL)], None),
+            Resolution::Command(c) if c == save));
+        assert!(matches!(shortcuts.resolve(&[chord(Keycode::F5, SHIFT)], None), Resolution::None));
+        // Bound, but nobody offers it
+        assert!(matches!(shortcuts.resolve(&[chord(Keycode::F6, 0)], None), Resolution::None));
+    }
+
+    #[test]
+    fn component_commands_need_focus() {
+        let mut shortcuts = shortcuts("B -> canvas.brush\nEscape -> app.quit");
+        let (canvas, other) = (component(), component());
+        shortcuts.register("canvas.brush", Some(&canvas));
+        shortcuts.register("app.quit", None);
+        // B is just typing for anything else
+        assert!(!press(&mut shortcuts, chord(Keycode::B, 0), Some(&other)));
+        assert!(!press(&mut shortcuts, chord(Keycode::B, 0), None));
+        assert!(press(&mut shortcuts, chord(Keycode::B, 0), Some(&canvas)));
+        // The host's go off regardless
+        assert!(press(&mut shortcuts, chord(Keycode::Escape, 0), Some(&other)));
+        assert_eq!(triggered(&shortcuts), [1, 1]);
+        // Registering again doesn't change who it's live for
+        assert_eq!(shortcuts.register("canvas.brush", Some(&canvas)), 1);
+        assert_eq!(shortcuts.commands[0].components.len(), 1);
+    }
+
+    #[test]
+    fn key_down_follows_sequences() {
+        let mut shortcuts = shortcuts("Ctrl+K Ctrl+S -> app.save\nCtrl+S -> app.other\nCtrl+Z -> app.undo");
+        shortcuts.register("app.save", None);
+        shortcuts.register("app.other", None);
+        shortcuts.register("app.undo", None);
+        let (ctrl_k, ctrl_s, ctrl_z) = (chord(Keycode::K, CTRL), chord(Keycode::S, CTRL), chord(Keycode::Z, CTRL));
+
+        // The first chord's taken while waiting on the rest
+        assert!(press(&mut shortcuts, ctrl_k, None));
+        assert_eq!(triggered(&shortcuts), [0, 0, 0]);
+        assert!(press(&mut shortcuts, ctrl_s, None));
+        assert_eq!(triggered(&shortcuts), [1, 0, 0]);
+        assert!(shortcuts.pending.is_empty());
+
+        // A sequence that goes wrong starts over from the key that broke it
+        assert!(press(&mut shortcuts, ctrl_k, None));
+        assert!(press(&mut shortcuts, ctrl_z, None));
+        assert_eq!(triggered(&shortcuts), [1, 0, 1]);
+        assert!(press(&mut shortcuts, ctrl_k, None));
+        assert!(!press(&mut shortcuts, chord(Keycode::A, 0), None));
+        assert!(shortcuts.pending.is_empty());
+        assert!(press(&mut shortcuts, ctrl_s, None));
+        assert_eq!(triggered(&shortcuts), [1, 1, 1]);
+
+        // Holding a single chord repeats it, and only what a shortcut took repeats
+        let now = Instant::now();
+        assert!(shortcuts.key_down(Scancode::Z, ctrl_z, false, None, now));
+        assert!(shortcuts.key_down(Scancode::Z, ctrl_z, true, None, now));
+        assert_eq!(triggered(&shortcuts), [1, 1, 3]);
+        assert!(!shortcuts.key_down(Scancode::A, chord(Keycode::A, 0), true, None, now));
+    }
+
+    #[test]
+    fn sequences_time_out() {
+        let mut shortcuts = shortcuts("Ctrl+K Ctrl+S -> app.save\nCtrl+S -> app.other");
+        shortcuts.register("app.save", None);
+        shortcuts.register("app.other", None);
+        let (ctrl_k, ctrl_s) = (chord(Keycode::K, CTRL), chord(Keycode::S, CTRL));
+        let start = Instant::now();
+        let mut press_at = |key, at| {
+            let taken = shortcuts.key_down(Scancode::Space, key, false, None, at);
+            shortcuts.held.clear();
+            taken
+        };
+
+        assert!(press_at(ctrl_k, start));
+        assert!(press_at(ctrl_s, start + SEQUENCE_TIMEOUT / 2));
+        // Too slow, so the second chord starts over on its own
+        assert!(press_at(ctrl_k, start + SEQUENCE_TIMEOUT));
+        assert!(press_at(ctrl_s, start + SEQUENCE_TIMEOUT * 2));
+        assert_eq!(triggered(&shortcuts), [1, 1]);
+    }
+
+    #[test]
+    fn focus_changes_drop_sequences() {
+        let mut shortcuts = shortcuts("Ctrl+K Ctrl+S -> canvas.save\nCtrl+S -> app.other");
+        let canvas = component();
+        shortcuts.register("canvas.save", Some(&canvas));
+        shortcuts.register("app.other", None);
+        let (ctrl_k, ctrl_s) = (chord(Keycode::K, CTRL), chord(Keycode::S, CTRL));
+
+        assert!(press(&mut shortcuts, ctrl_k, Some(&canvas)));
+        shortcuts.focus_changed();
+        assert!(press(&mut shortcuts, ctrl_s, Some(&canvas)));
+        assert_eq!(triggered(&shortcuts), [0, 1]);
+    }
+
+    #[test]
+    fn lists_conflicts() {
+        let mut shortcuts = shortcuts("Ctrl+K -> a\nCtrl+K Ctrl+S -> b\nF5 -> c\nF5 -> d\nF5 -> c\nF6 -> e");
+        for name in &["a", "b", "c", "d"] {
+            shortcuts.register(name, None);
+        }
+        assert_eq!(shortcuts.conflicts(), [
+            "test:2: Ctrl+K Ctrl+S (b) can't go off, Ctrl+K (a, line 1) goes off first",
+            "test:4: F5 (d) can't go off, it's bound to c on line 3 too",
+            "test:5: F5 (c) can't go off, it's bound to d on line 4 too",
+            "test:6: F6 is bound to e, which nothing registered",
+        ]);
+    }
+}
@@ -0,0 +1,362 @@
+// Text layout and glyph atlases
+//
+// Glyphs are rasterized by FreeType (through SDL_ttf) when a font file is
+// available, otherwise they come from a built-in 5x7 bitmap font.
+
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    ttf::Sdl2TtfContext,
+};
+use std::collections::HashMap;
+
+pub const FONT_PATH: &str = "resources/fonts/default.ttf";
+
+const ATLAS_WIDTH: i32 = 256;
+const FIRST_CHAR: u8 = b' ';
+const LAST_CHAR: u8 = b'~';
+// Drawn for anything the atlas doesn't have
+const MISSING_CHAR: char = '?';
+
+// A glyph's pixel rect in its atlas, top-down, and how far it moves the pen
+#[derive(Clone, Copy)]
+struct Glyph {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    advance: i32,
+}
+
+// A glyph quad ready to draw: destination rect in pixels, source rect in UVs
+pub struct GlyphQuad {
+    pub dest: [f32; 4],
+    pub source: [f32; 4],
+}
+
+pub struct Atlas {
+    // White, with coverage in alpha. Follows the image pixel format, so rows
+    // are stored bottom-up.
+    pub pixels: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+    // Bitmap glyphs want NEAREST filtering, rasterized ones LINEAR
+    pub smooth: bool,
+    glyphs: HashMap<char, Glyph>,
+    line_height: i32,
+    // Size the glyphs were rasterized at; other sizes scale from it
+    pixel_size: f32,
+}
+
+// A glyph's coverage, before packing, top-down
+struct GlyphBitmap {
+    c: char,
+    w: i32,
+    h: i32,
+    coverage: Vec<u8>,
+    advance: i32,
+}
+
+impl Atlas {
+    pub fn bitmap() -> Atlas {
+        let mut bitmaps = Vec::new();
+        for (i, rows) in BITMAP_FONT.iter().enumerate() {
+            // 1px of spacing to the right and below each 5x7 glyph
+            let (w, h) = (BITMAP_GLYPH_W + 1, BITMAP_GLYPH_H + 1);
+            let mut coverage = vec![0; (w * h) as usize];
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..BITMAP_GLYPH_W {
+                    if row & (1 << (BITMAP_GLYPH_W - 1 - x)) != 0 {
+                        coverage[y * w as usize + x as usize] = 0xff;
+                    }
+                }
+            }
+            let c = (FIRST_CHAR + i as u8) as char;
+            bitmaps.push(GlyphBitmap { c, w, h, coverage, advance: w });
+        }
+        let line_height = BITMAP_GLYPH_H + 1;
+        Atlas::pack(bitmaps, line_height, line_height as f32, false)
+    }
+
+    pub fn rasterize(ttf: &Sdl2TtfContext, path: &str, pixel_size: u16) -> Result<Atlas, String> {
+        let font = ttf.load_font(path, pixel_size)?;
+        let mut bitmaps = Vec::new();
+        for c in FIRST_CHAR..=LAST_CHAR {
+            let c = c as char;
+            let advance = font.find_glyph_metrics(c).map_or(0, |m| m.advance);
+            // Whitespace has nothing to render, and SDL_ttf errors on it
+            let surface = match font.render_char(c).blended(Color::RGBA(255, 255, 255, 255)) {
+                Ok(surface) => surface.convert_format(PixelFormatEnum::RGBA32)?,
+                Err(_) => {
+                    bitmaps.push(GlyphBitmap { c, w: 0, h: 0, coverage: Vec::new(), advance });
+                    continue;
+                }
+            };
+            let (w, h, pitch) = (surface.width() as usize, surface.height() as usize, surface.pitch() as usize);
+            let mut coverage = Vec::with_capacity(w * h);
+            surface.with_lock(|pixels| {
+                for y in 0..h {
+                    for x in 0..w {
+                        coverage.push(pixels[y * pitch + x * 4 + 3]);
+                    }
+                }
+            });
+            bitmaps.push(GlyphBitmap { c, w: w as i32, h: h as i32, coverage, advance });
+        }
+        Ok(Atlas::pack(bitmaps, font.recommended_line_spacing(), pixel_size as f32, true))
+    }
+
+    // Shelf-packs glyphs into rows of ATLAS_WIDTH, or of the widest glyph if
+    // that's wider, as it can be at big sizes
+    fn pack(bitmaps: Vec<GlyphBitmap>, line_height: i32, pixel_size: f32, smooth: bool) -> Atlas {
+        let width = bitmaps.iter().map(|bitmap| bitmap.w).fold(ATLAS_WIDTH, i32::max);
+        let mut glyphs = HashMap::new();
+        let (mut x, mut y, mut shelf_h) = (0, 0, 0);
+        for bitmap in &bitmaps {
+            if x + bitmap.w > width {
+                x = 0;
+                y += shelf_h;
+                shelf_h = 0;
+            }
+            glyphs.insert(bitmap.c, Glyph { x, y, w: bitmap.w, h: bitmap.h, advance: bitmap.advance });
+            x += bitmap.w;
+            shelf_h = shelf_h.max(bitmap.h);
+        }
+        let height = (y + shelf_h).max(1);
+
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        for bitmap in &bitmaps {
+            let glyph = glyphs[&bitmap.c];
+            for gy in 0..bitmap.h {
+                // Flip, since atlas rows are stored bottom-up
+                let row = height - 1 - (glyph.y + gy);
+                for gx in 0..bitmap.w {
+                    let i = ((row * width + glyph.x + gx) * 4) as usize;
+                    pixels[i..i + 3].copy_from_slice(&[0xff, 0xff, 0xff]);
+                    pixels[i + 3] = bitmap.coverage[(gy * bitmap.w + gx) as usize];
+                }
+            }
+        }
+        Atlas { pixels, width, height, smooth, glyphs, line_height, pixel_size }
+    }
+
+    fn glyph(&self, c: char) -> Glyph {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&MISSING_CHAR)).cloned().unwrap()
+    }
+
+    // Lays out text with (x, y) at its top-left, lines running downward
+    pub fn layout(&self, text: &str, x: f32, y: f32, size: f32) -> Vec<GlyphQuad> {
+        let scale = size / self.pixel_size;
+        let line_height = self.line_height as f32 * scale;
+        let (mut pen_x, mut top) = (x, y);
+        let mut quads = Vec::new();
+        for c in text.chars() {
+            if c == '\n' {
+                pen_x = x;
+                top -= line_height;
+                continue;
+            }
+            let glyph = self.glyph(c);
+            if glyph.w > 0 && glyph.h > 0 {
+                let (w, h) = (glyph.w as f32 * scale, glyph.h as f32 * scale);
+                let (atlas_w, atlas_h) = (self.width as f32, self.height as f32);
+                quads.push(GlyphQuad {
+                    dest: [pen_x, top - h, w, h],
+                    source: [
+                        glyph.x as f32 / atlas_w,
+                        (self.height - glyph.y - glyph.h) as f32 / atlas_h,
+                        glyph.w as f32 / atlas_w,
+                        glyph.h as f32 / atlas_h,
+                    ],
+                });
+            }
+            pen_x += glyph.advance as f32 * scale;
+        }
+        quads
+    }
+
+    // Width of the longest line, and height of all lines
+    pub fn measure(&self, text: &str, size: f32) -> (f32, f32) {
+        let scale = size / self.pixel_size;
+        let mut width: f32 = 0.0;
+        let mut lines = 0;
+        for line in text.split('\n') {
+            let advance: i32 = line.chars().map(|c| self.glyph(c).advance).sum();
+            width = width.max(advance as f32 * scale);
+            lines += 1;
+        }
+        (width, (lines * self.line_height) as f32 * scale)
+    }
+}
+
+const BITMAP_GLYPH_W: i32 = 5;
+const BITMAP_GLYPH_H: i32 = 7;
+
+// One row per byte, top to bottom, leftmost pixel in bit 4.
+// Covers FIRST_CHAR through LAST_CHAR.
+const BITMAP_FONT: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // '#'
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // '&'
+    [0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // '0'
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // '1'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // '2'
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // '3'
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // '4'
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // '5'
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // '6'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // '8'
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // '9'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // ':'
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // '@'
+    [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'A'
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // 'B'
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // 'C'
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // 'D'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // 'E'
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // 'F'
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // 'G'
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // 'H'
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // 'L'
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'O'
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // 'P'
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // 'Q'
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // 'R'
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // 'S'
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // 'W'
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // 'Y'
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // 'Z'
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ']'
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // '_'
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f], // 'a'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e], // 'b'
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e], // 'c'
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f], // 'd'
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e], // 'e'
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08], // 'f'
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'g'
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // 'h'
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e], // 'i'
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c], // 'j'
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // 'k'
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // 'l'
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11], // 'm'
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // 'n'
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e], // 'o'
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10], // 'p'
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01], // 'q'
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // 'r'
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e], // 's'
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06], // 't'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d], // 'u'
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04], // 'v'
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a], // 'w'
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11], // 'x'
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e], // 'y'
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f], // 'z'
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // '{'
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // '|'
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // '}'
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(c: char, w: i32, h: i32) -> GlyphBitmap {
+        GlyphBitmap { c, w, h, coverage: vec![0x80; (w * h) as usize], advance: w }
+    }
+
+    // Coverage at (x, y) of the atlas, top-down
+    fn coverage(atlas: &Atlas, x: i32, y: i32) -> u8 {
+        atlas.pixels[(((atlas.height - 1 - y) * atlas.width + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn packs_into_shelves() {
+        let atlas = Atlas::pack(vec![solid('a', 100, 4), solid('b', 100, 6), solid('c', 100, 2)], 8, 8.0, true);
+        assert_eq!((atlas.width, atlas.height), (ATLAS_WIDTH, 8));
+        let positions: Vec<(i32, i32)> = "abc".chars().map(|c| (atlas.glyphs[&c].x, atlas.glyphs[&c].y)).collect();
+        assert_eq!(positions, [(0, 0), (100, 0), (0, 6)]);
+        assert_eq!(coverage(&atlas, 0, 0), 0x80);
+        assert_eq!(coverage(&atlas, 99, 3), 0x80);
+        // Below 'a' on its shelf, which 'b' is taller than
+        assert_eq!(coverage(&atlas, 0, 4), 0);
+        assert_eq!(coverage(&atlas, 100, 5), 0x80);
+    }
+
+    #[test]
+    fn widens_for_glyphs_wider_than_the_atlas() {
+        let atlas = Atlas::pack(vec![solid('a', 10, 3), solid('W', 300, 2), solid('b', 5, 1)], 8, 300.0, true);
+        assert_eq!((atlas.width, atlas.height), (300, 6));
+        assert_eq!((atlas.glyphs[&'W'].x, atlas.glyphs[&'W'].y), (0, 3));
+        assert_eq!((atlas.glyphs[&'b'].x, atlas.glyphs[&'b'].y), (0, 5));
+        assert_eq!(coverage(&atlas, 299, 4), 0x80);
+        assert_eq!(atlas.pixels.len(), (300 * 6 * 4) as usize);
+    }
+
+    #[test]
+    fn lays_out_lines_downward() {
+        let atlas = Atlas::bitmap();
+        // 6x8 cells at the bitmap font's own size
+        let quads = atlas.layout("AB\nC", 10.0, 100.0, 8.0);
+        let dests: Vec<[f32; 4]> = quads.iter().map(|quad| quad.dest).collect();
+        assert_eq!(dests, [[10.0, 92.0, 6.0, 8.0], [16.0, 92.0, 6.0, 8.0], [10.0, 84.0, 6.0, 8.0]]);
+        // Twice the size, twice as far apart
+        let quads = atlas.layout("AB", 0.0, 0.0, 16.0);
+        assert_eq!(quads[1].dest, [12.0, -16.0, 12.0, 16.0]);
+
+        // The source rect finds the glyph in the atlas
+        let glyph = atlas.glyphs[&'A'];
+        let source = quads[0].source;
+        assert_eq!(source[0] * atlas.width as f32, glyph.x as f32);
+        assert_eq!((source[1] + source[3]) * atlas.height as f32, (atlas.height - glyph.y) as f32);
+        assert_eq!(source[2] * atlas.width as f32, 6.0);
+    }
+
+    #[test]
+    fn missing_chars_draw_as_question_marks() {
+        let atlas = Atlas::bitmap();
+        assert_eq!(atlas.layout("é", 0.0, 0.0, 8.0)[0].source, atlas.layout("?", 0.0, 0.0, 8.0)[0].source);
+    }
+
+    #[test]
+    fn measures_the_longest_line() {
+        let atlas = Atlas::bitmap();
+        assert_eq!(atlas.measure("AB\nCDE\n", 8.0), (18.0, 24.0));
+        assert_eq!(atlas.measure("AB\nCDE\n", 16.0), (36.0, 48.0));
+        assert_eq!(atlas.measure("", 8.0), (0.0, 8.0));
+    }
+}
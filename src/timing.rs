@@ -0,0 +1,180 @@
+// Frame pacing, and the "time" imports
+//
+// Frames are paced by vsync when the window has it, or else by sleeping out the
+// rest of each frame. A component's optional fixedUpdate export runs on a fixed
+// timestep, as many times as real time calls for; update runs once per drawn
+// frame. When nothing's animating, the main loop waits for input rather than
+// drawing frames that would come out the same.
+
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use wasmtime::*;
+
+use crate::component::ImportModule;
+
+// How often fixedUpdate runs, and the frame rate without vsync
+pub const FIXED_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// After a stall (a breakpoint, a slow frame), time only moves on this many
+// steps, the rest is dropped
+const MAX_STEPS: u32 = 5;
+
+pub enum Pacing {
+    // Swapping buffers waits for the display, there's nothing left to wait for
+    VSync,
+    // Sleeps out whatever's left of FIXED_STEP after each frame
+    Capped,
+    // Frames are exactly one step apart, however long they really took. Headless
+    // runs use this, so they come out the same every time.
+    Simulated,
+}
+
+// What the time imports report
+#[derive(Default)]
+struct TimeState {
+    // Seconds since the first frame, less time spent idle or stalled
+    elapsed: f64,
+    // Seconds covered by the update (or fixedUpdate) being run
+    delta: f32,
+    // Set by requestFrame, for the next frame
+    animating: bool,
+}
+thread_local! {
+    static TIME: RefCell<TimeState> = RefCell::new(TimeState::default());
+}
+
+pub struct Clock {
+    pacing: Pacing,
+    frame_start: Instant,
+    // Time not yet covered by fixed steps
+    accumulator: Duration,
+    elapsed: Duration,
+    // The loop waited for input since the last frame
+    idled: bool,
+}
+
+impl Clock {
+    pub fn new(pacing: Pacing) -> Clock {
+        // There's nothing on screen yet, so the first frame can't wait
+        TIME.with(|time| time.borrow_mut().animating = true);
+        Clock { pacing, frame_start: Instant::now(), accumulator: Duration::from_secs(0), elapsed: Duration::from_secs(0), idled: false }
+    }
+
+    // Starts a frame, moving time on by however long the last one took. Returns
+    // how many times to run fixedUpdate.
+    pub fn begin_frame(&mut self) -> u32 {
+        let now = Instant::now();
+        let delta = match self.pacing {
+            Pacing::Simulated => FIXED_STEP,
+            // Nothing was animating while we waited, so pick up as if from the
+            // frame before
+            _ if self.idled => FIXED_STEP,
+            _ => (now - self.frame_start).min(FIXED_STEP * MAX_STEPS),
+        };
+        self.frame_start = now;
+        self.idled = false;
+        self.elapsed += delta;
+        self.accumulator += delta;
+
+        let mut steps = 0;
+        while self.accumulator >= FIXED_STEP {
+            self.accumulator -= FIXED_STEP;
+            steps += 1;
+        }
+        TIME.with(|time| {
+            let mut time = time.borrow_mut();
+            time.elapsed = self.elapsed.as_secs_f64();
+            time.delta = delta.as_secs_f32();
+            time.animating = false;
+        });
+        steps
+    }
+
+    // Waits out the rest of the frame, if it's up to us
+    pub fn end_frame(&self) {
+        if let Pacing::Capped = self.pacing {
+            if let Some(left) = FIXED_STEP.checked_sub(self.frame_start.elapsed()) {
+                std::thread::sleep(left);
+            }
+        }
+    }
+
+    // Whether the loop can wait for input before the next frame: nothing asked
+    // for one, and there's input to wait for
+    pub fn can_idle(&self) -> bool {
+        let animating = TIME.with(|time| time.borrow().animating);
+        !animating && !matches!(self.pacing, Pacing::Simulated)
+    }
+
+    // Call after waiting for input, so the time spent waiting doesn't count
+    pub fn idled(&mut self) {
+        self.idled = true;
+    }
+}
+
+// Runs `f` as one fixed step, so the imports report FIXED_STEP as its delta
+pub fn fixed_step<T, F: FnOnce() -> T>(f: F) -> T {
+    let frame_delta = TIME.with(|time| std::mem::replace(&mut time.borrow_mut().delta, FIXED_STEP.as_secs_f32()));
+    let ret = f();
+    TIME.with(|time| time.borrow_mut().delta = frame_delta);
+    ret
+}
+
+pub fn import_module(store: &Store) -> ImportModule {
+    let mut ret = ImportModule::new();
+    // Seconds since the first frame. Doesn't move during a frame.
+    ret.add_func("elapsed", Func::wrap(store, || TIME.with(|time| time.borrow().elapsed)));
+    // Seconds since the last update, or the fixed step inside fixedUpdate
+    ret.add_func("delta", Func::wrap(store, || TIME.with(|time| time.borrow().delta)));
+    // Asks for another frame straight after this one. Without any asking, the
+    // next frame waits for input.
+    ret.add_func("requestFrame", Func::wrap(store, || {
+        TIME.with(|time| time.borrow_mut().animating = true);
+    }));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta() -> f32 {
+        TIME.with(|time| time.borrow().delta)
+    }
+
+    #[test]
+    fn simulated_frames_are_one_step() {
+        let mut clock = Clock::new(Pacing::Simulated);
+        std::thread::sleep(FIXED_STEP * 2);
+        for frame in 1..=10 {
+            assert_eq!(clock.begin_frame(), 1);
+            assert_eq!(delta(), FIXED_STEP.as_secs_f32());
+            assert_eq!(clock.elapsed, FIXED_STEP * frame);
+        }
+        assert!(!clock.can_idle());
+    }
+
+    #[test]
+    fn stalls_catch_up_at_most_max_steps() {
+        let mut clock = Clock::new(Pacing::Capped);
+        clock.frame_start -= Duration::from_secs(1);
+        assert_eq!(clock.begin_frame(), MAX_STEPS);
+        assert_eq!(delta(), (FIXED_STEP * MAX_STEPS).as_secs_f32());
+        // The rest was dropped rather than carried over
+        assert!(clock.accumulator < FIXED_STEP);
+        assert_eq!(clock.elapsed, FIXED_STEP * MAX_STEPS);
+    }
+
+    #[test]
+    fn idling_doesnt_count() {
+        let mut clock = Clock::new(Pacing::VSync);
+        clock.begin_frame();
+        assert!(clock.can_idle());
+        clock.frame_start -= Duration::from_secs(10);
+        clock.idled();
+        assert_eq!(clock.begin_frame(), 1);
+        assert_eq!(delta(), FIXED_STEP.as_secs_f32());
+    }
+}